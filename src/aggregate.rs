@@ -0,0 +1,327 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::entity::{Entity, FieldData, FieldType};
+use crate::error::QueryError;
+use crate::schema::EntityDeclaration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DateTruncation {
+    Month,
+    Year,
+}
+
+/// Aggregations over every entity matching a query, regardless of paging.
+/// Each list names the fields to aggregate, which may be paths into objects.
+#[derive(Default, Debug, Deserialize)]
+pub struct Aggregate {
+    #[serde(default)]
+    pub count: bool,
+
+    #[serde(default)]
+    pub distinct: Vec<String>,
+
+    #[serde(default)]
+    pub min: Vec<String>,
+
+    #[serde(default)]
+    pub max: Vec<String>,
+
+    #[serde(default)]
+    pub sum: Vec<String>,
+
+    #[serde(default)]
+    pub avg: Vec<String>,
+
+    /// Field to additionally aggregate each group of entities sharing a value by.
+    /// Entities are grouped under each item of list fields.
+    pub group_by: Option<String>,
+
+    /// Groups dates by their month or year instead of the full date.
+    pub truncate: Option<DateTruncation>,
+}
+
+#[derive(Default, Debug, Serialize)]
+pub struct AggregateResult<'a> {
+    #[serde(flatten)]
+    pub all: Bucket<'a>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub groups: Option<Vec<AggregateGroup<'a>>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AggregateGroup<'a> {
+    /// Value of the `group_by` field, or `None` for entities without it.
+    pub key: Option<FieldData>,
+
+    #[serde(flatten)]
+    pub bucket: Bucket<'a>,
+}
+
+#[derive(Default, Debug, Serialize)]
+pub struct Bucket<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count: Option<usize>,
+
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub distinct: HashMap<&'a str, Vec<&'a FieldData>>,
+
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub min: HashMap<&'a str, Option<&'a FieldData>>,
+
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub max: HashMap<&'a str, Option<&'a FieldData>>,
+
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub sum: HashMap<&'a str, f64>,
+
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub avg: HashMap<&'a str, Option<f64>>,
+}
+
+impl Aggregate {
+    pub fn evaluate<'a>(
+        &'a self,
+        entities: &[(&'a String, &'a Entity)],
+        decl: &EntityDeclaration
+    ) -> Result<AggregateResult<'a>, QueryError> {
+        self.check(decl)?;
+
+        let entities: Vec<&Entity> = entities.iter().map(|(_, entity)| *entity).collect();
+
+        let groups = match &self.group_by {
+            Some(field) => Some(self.groups(field, &entities)),
+            None => None
+        };
+
+        Ok(AggregateResult {
+            all: self.bucket(&entities),
+            groups,
+        })
+    }
+
+    /// Checks that every aggregated field exists and has a type the
+    /// aggregation applies to.
+    fn check(&self, decl: &EntityDeclaration) -> Result<(), QueryError> {
+        let item_type = |field: &str| decl.get_field(&path(field))
+            .map(|f| f.ty.item_type())
+            .ok_or_else(|| QueryError::UnknownField(field.to_owned()));
+
+        let invalid = |aggregation: &str, field: &str, ty: &FieldType| QueryError::InvalidAggregate(
+            format!(r#""{aggregation}" can't be applied to "{field}" of type "{ty}""#)
+        );
+
+        let distinct = self.distinct.iter().map(|field| ("distinct", field));
+        let group_by = self.group_by.iter().map(|field| ("group_by", field));
+
+        for (aggregation, field) in distinct.chain(group_by) {
+            match item_type(field)? {
+                ty @ FieldType::Bin | ty @ FieldType::Object | ty @ FieldType::Backlink =>
+                    return Err(invalid(aggregation, field, ty)),
+
+                _ => ()
+            }
+        }
+
+        let min = self.min.iter().map(|field| ("min", field));
+        let max = self.max.iter().map(|field| ("max", field));
+
+        for (aggregation, field) in min.chain(max) {
+            match item_type(field)? {
+                FieldType::Num | FieldType::Date | FieldType::DateTime => (),
+
+                ty => return Err(invalid(aggregation, field, ty))
+            }
+        }
+
+        let sum = self.sum.iter().map(|field| ("sum", field));
+        let avg = self.avg.iter().map(|field| ("avg", field));
+
+        for (aggregation, field) in sum.chain(avg) {
+            match item_type(field)? {
+                FieldType::Num => (),
+
+                ty => return Err(invalid(aggregation, field, ty))
+            }
+        }
+
+        match (&self.group_by, self.truncate) {
+            (None, Some(_)) =>
+                Err(QueryError::InvalidAggregate("\"truncate\" requires \"group_by\"".to_owned())),
+
+            (Some(field), Some(_)) => match item_type(field)? {
+                FieldType::Date | FieldType::DateTime => Ok(()),
+
+                ty => Err(invalid("truncate", field, ty))
+            },
+
+            _ => Ok(())
+        }
+    }
+
+    fn bucket<'a>(&'a self, entities: &[&'a Entity]) -> Bucket<'a> {
+        let mut bucket = Bucket::default();
+
+        if self.count {
+            bucket.count = Some(entities.len());
+        }
+
+        for field in &self.distinct {
+            let mut values = values(field, entities);
+
+            values.sort_by(|lhs, rhs| lhs.compare(rhs).unwrap_or(Ordering::Equal));
+            values.dedup_by(|lhs, rhs| lhs.compare(rhs) == Some(Ordering::Equal));
+
+            bucket.distinct.insert(field, values);
+        }
+
+        for field in &self.min {
+            bucket.min.insert(field, extreme(values(field, entities), Ordering::Less));
+        }
+
+        for field in &self.max {
+            bucket.max.insert(field, extreme(values(field, entities), Ordering::Greater));
+        }
+
+        for field in &self.sum {
+            bucket.sum.insert(field, numbers(field, entities).iter().sum());
+        }
+
+        for field in &self.avg {
+            let numbers = numbers(field, entities);
+
+            let avg = match numbers.len() {
+                0 => None,
+                len => Some(numbers.iter().sum::<f64>() / len as f64)
+            };
+
+            bucket.avg.insert(field, avg);
+        }
+
+        bucket
+    }
+
+    /// Buckets of the entities sharing each value of `field`, ordered by value
+    /// with entities lacking the field, or with an empty list, last.
+    fn groups<'a>(&'a self, field: &str, entities: &[&'a Entity]) -> Vec<AggregateGroup<'a>> {
+        let path = path(field);
+        let mut keyed: Vec<(Option<FieldData>, &Entity)> = vec![];
+
+        for entity in entities {
+            let data = match entity.get_path(&path) {
+                Some(data) => data,
+                None => {
+                    keyed.push((None, entity));
+                    continue;
+                }
+            };
+
+            // Lists repeating a value only count the entity once in its group,
+            // and empty lists are grouped with missing fields
+            let mut keys: Vec<Option<FieldData>> = vec![];
+
+            for item in flatten(data) {
+                let key = Some(self.group_key(item));
+
+                if !keys.iter().any(|other| same_key(other, &key)) {
+                    keys.push(key);
+                }
+            }
+
+            if keys.is_empty() {
+                keys.push(None);
+            }
+
+            keyed.extend(keys.into_iter().map(|key| (key, *entity)));
+        }
+
+        keyed.sort_by(|(lhs, _), (rhs, _)| match (lhs, rhs) {
+            (Some(lhs), Some(rhs)) => lhs.compare(rhs).unwrap_or(Ordering::Equal),
+            (lhs, rhs) => lhs.is_none().cmp(&rhs.is_none())
+        });
+
+        let mut groups: Vec<(Option<FieldData>, Vec<&Entity>)> = vec![];
+
+        for (key, entity) in keyed {
+            match groups.last_mut() {
+                Some((last, members)) if same_key(last, &key) => members.push(entity),
+                _ => groups.push((key, vec![entity]))
+            }
+        }
+
+        groups.into_iter()
+            .map(|(key, members)| AggregateGroup {
+                key,
+                bucket: self.bucket(&members),
+            })
+            .collect()
+    }
+
+    fn group_key(&self, data: &FieldData) -> FieldData {
+        let format = match self.truncate {
+            Some(DateTruncation::Month) => "%Y-%m",
+            Some(DateTruncation::Year) => "%Y",
+            None => return data.clone()
+        };
+
+        match data {
+            FieldData::Date(d) => FieldData::Str(d.format(format).to_string()),
+            FieldData::DateTime(d) => FieldData::Str(d.format(format).to_string()),
+
+            _ => data.clone()
+        }
+    }
+}
+
+fn path(field: &str) -> Vec<&str> {
+    field.split('.').collect()
+}
+
+fn same_key(lhs: &Option<FieldData>, rhs: &Option<FieldData>) -> bool {
+    match (lhs, rhs) {
+        (Some(lhs), Some(rhs)) => lhs.compare(rhs) == Some(Ordering::Equal),
+        (None, None) => true,
+
+        _ => false
+    }
+}
+
+/// The value of a field, or its items for lists.
+fn flatten(data: &FieldData) -> Vec<&FieldData> {
+    match data {
+        FieldData::List(items) => items.iter().flat_map(flatten).collect(),
+
+        _ => vec![data]
+    }
+}
+
+fn values<'a>(field: &str, entities: &[&'a Entity]) -> Vec<&'a FieldData> {
+    let path = path(field);
+
+    entities.iter()
+        .filter_map(|entity| entity.get_path(&path))
+        .flat_map(flatten)
+        .collect()
+}
+
+fn numbers(field: &str, entities: &[&Entity]) -> Vec<f64> {
+    values(field, entities).into_iter()
+        .filter_map(|data| match data {
+            FieldData::Num(n) => Some(*n),
+            _ => None
+        })
+        .collect()
+}
+
+/// The value every other value compares as `wanted` against, e.g. the minimum
+/// for `Ordering::Less`.
+fn extreme(values: Vec<&FieldData>, wanted: Ordering) -> Option<&FieldData> {
+    values.into_iter().fold(None, |best, value| match best {
+        Some(best) if value.compare(best) != Some(wanted) => Some(best),
+        _ => Some(value)
+    })
+}
@@ -0,0 +1,258 @@
+use rocket::http::uri::Uri;
+use serde::{Serialize, Serializer};
+
+use crate::conditional::content_hash;
+use crate::entity::{Entity, FieldData};
+use crate::mime;
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Where a binary field is served on its own: a top-level field of an entity,
+/// or an item of a top-level list.
+#[derive(Clone, Copy, Debug)]
+pub struct FieldRoute<'a> {
+    pub ty: &'a str,
+    pub id: &'a str,
+    pub entity: &'a Entity,
+    pub field: &'a str,
+    pub index: Option<usize>,
+}
+
+impl<'a> FieldRoute<'a> {
+    pub fn item(self, index: usize) -> FieldRoute<'a> {
+        FieldRoute { index: Some(index), ..self }
+    }
+
+    /// Key of the field in the entity's recorded file extensions.
+    fn key(&self) -> String {
+        match self.index {
+            Some(index) => format!("{}/{index}", self.field),
+            None => self.field.to_owned()
+        }
+    }
+
+    fn url(&self) -> String {
+        let url = field_url(self.ty, self.id, self.field);
+
+        match self.index {
+            Some(index) => format!("{url}/{index}"),
+            None => url
+        }
+    }
+}
+
+/// URL of the route serving a top-level field of an entity.
+pub fn field_url(ty: &str, id: &str, field: &str) -> String {
+    format!("/ent/{}/{}/{}", Uri::percent_encode(ty), Uri::percent_encode(id), Uri::percent_encode(field))
+}
+
+/// A binary field in a JSON result, serialized as a description of its
+/// content rather than the content itself, e.g.
+/// `{ "url": "/ent/Post/p1/logo", "mime": "image/png", "size": 4096, "hash": "...", "width": 64, "height": 64 }`.
+#[derive(Debug)]
+pub struct Asset<'a> {
+    pub data: &'a FieldData,
+
+    /// Route serving the content, or `None` for fields in objects, which
+    /// can't be requested on their own.
+    pub route: Option<FieldRoute<'a>>,
+
+    /// Whether to include the content as well, base64-encoded. Only content
+    /// held in memory is included; streamed files are left to their `url`.
+    pub base64: bool,
+}
+
+#[derive(Serialize)]
+struct Description {
+    url: Option<String>,
+    mime: String,
+    size: u64,
+    hash: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    width: Option<u32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    height: Option<u32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    base64: Option<String>,
+}
+
+impl Serialize for Asset<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let (size, hash, dimensions) = match self.data {
+            FieldData::Bin(bytes) => (bytes.len() as u64, content_hash(bytes), dimensions(bytes)),
+            FieldData::File(file) => (file.size, file.hash.clone(), file.dimensions),
+
+            _ => return self.data.serialize(serializer)
+        };
+
+        // Streamed files are too large to inline, and aren't read while serializing
+        let content = match (self.base64, self.data) {
+            (true, FieldData::Bin(bytes)) => Some(bytes),
+
+            _ => None
+        };
+
+        let key = self.route.map(|route| route.key());
+        let extension = key.and_then(|key| self.route?.entity.extensions.get(&key));
+
+        Description {
+            url: self.route.map(|route| route.url()),
+            mime: mime::content_type(self.data, extension.map(|ext| ext.as_str())),
+            size,
+            hash,
+            width: dimensions.map(|(width, _)| width),
+            height: dimensions.map(|(_, height)| height),
+            base64: content.map(|content| encode_base64(content)),
+        }.serialize(serializer)
+    }
+}
+
+/// Width and height of an image, read from the header at the start of its
+/// data. Formats without a fixed header, such as SVG, aren't recognized.
+pub fn dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    let u16_le = |i: usize| data.get(i..i + 2).map(|b| u32::from(u16::from_le_bytes([b[0], b[1]])));
+    let u16_be = |i: usize| data.get(i..i + 2).map(|b| u32::from(u16::from_be_bytes([b[0], b[1]])));
+    let u24_le = |i: usize| data.get(i..i + 3).map(|b| u32::from_le_bytes([b[0], b[1], b[2], 0]));
+    let u32_le = |i: usize| data.get(i..i + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+    let u32_be = |i: usize| data.get(i..i + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]));
+
+    match mime::sniff(data)? {
+        "image/png" if data.get(12..16) == Some(b"IHDR") => Some((u32_be(16)?, u32_be(20)?)),
+        "image/gif" => Some((u16_le(6)?, u16_le(8)?)),
+
+        // Rows are stored bottom-up if the height is positive, top-down if negative
+        "image/bmp" => Some((u32_le(18)?, (u32_le(22)? as i32).unsigned_abs())),
+
+        "image/webp" => match data.get(12..16)? {
+            b"VP8 " => Some((u16_le(26)? & 0x3fff, u16_le(28)? & 0x3fff)),
+            b"VP8L" => u32_le(21).map(|bits| ((bits & 0x3fff) + 1, ((bits >> 14) & 0x3fff) + 1)),
+            b"VP8X" => Some((u24_le(24)? + 1, u24_le(27)? + 1)),
+
+            _ => None
+        },
+
+        "image/jpeg" => {
+            // Step through the segments before the image to the frame header
+            let mut i = 2;
+
+            while *data.get(i)? == 0xff {
+                match *data.get(i + 1)? {
+                    // Padding before a marker
+                    0xff => i += 1,
+
+                    // Start of frame, apart from the table markers among them
+                    marker @ 0xc0..=0xcf if !matches!(marker, 0xc4 | 0xc8 | 0xcc) =>
+                        return Some((u16_be(i + 7)?, u16_be(i + 5)?)),
+
+                    _ => i += 2 + u16_be(i + 2)? as usize
+                }
+            }
+
+            None
+        },
+
+        _ => None
+    }
+}
+
+pub fn encode_base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity((bytes.len() + 2) / 3 * 4);
+
+    for chunk in bytes.chunks(3) {
+        let byte = |i: usize| u32::from(chunk.get(i).copied().unwrap_or(0));
+        let bits = byte(0) << 16 | byte(1) << 8 | byte(2);
+
+        // Each byte of the chunk spans into one more character, padded to four
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => encoded.push(BASE64_ALPHABET[(bits >> (18 - 6 * i) & 0x3f) as usize] as char),
+                false => encoded.push('=')
+            }
+        }
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_base64_tails() {
+        assert_eq!(encode_base64(b""), "");
+        assert_eq!(encode_base64(b"f"), "Zg==");
+        assert_eq!(encode_base64(b"fo"), "Zm8=");
+        assert_eq!(encode_base64(b"foo"), "Zm9v");
+        assert_eq!(encode_base64(b"foob"), "Zm9vYg==");
+        assert_eq!(encode_base64(&[0xfb, 0xff, 0xbf]), "+/+/");
+    }
+
+    #[test]
+    fn dimensions_png() {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        png.extend_from_slice(&640u32.to_be_bytes());
+        png.extend_from_slice(&480u32.to_be_bytes());
+
+        assert_eq!(dimensions(&png), Some((640, 480)));
+    }
+
+    #[test]
+    fn dimensions_gif() {
+        assert_eq!(dimensions(b"GIF89a\x0c\0\x07\0"), Some((12, 7)));
+    }
+
+    #[test]
+    fn dimensions_bmp() {
+        let mut bmp = b"BM\0\0\0\0\0\0\0\0\x36\0\0\0".to_vec();
+        bmp.extend_from_slice(&40u32.to_le_bytes());
+        bmp.extend_from_slice(&40i32.to_le_bytes());
+
+        // Top-down bitmaps have a negative height
+        bmp.extend_from_slice(&(-30i32).to_le_bytes());
+
+        assert_eq!(dimensions(&bmp), Some((40, 30)));
+    }
+
+    fn webp(chunk: &[u8], header: &[u8]) -> Vec<u8> {
+        let mut webp = b"RIFF\0\0\0\0WEBP".to_vec();
+        webp.extend_from_slice(chunk);
+        webp.extend_from_slice(&[0; 4]);
+        webp.extend_from_slice(header);
+        webp
+    }
+
+    #[test]
+    fn dimensions_webp() {
+        // Lossy: a frame tag and start code before 14-bit sizes
+        let vp8 = webp(b"VP8 ", b"\0\0\0\x9d\x01\x2a\x78\0\x5a\0");
+        assert_eq!(dimensions(&vp8), Some((120, 90)));
+
+        // Lossless: a signature byte before the sizes less one, packed in 14 bits each
+        let bits: u32 = (63 - 1) | (47 - 1) << 14;
+        let vp8l = webp(b"VP8L", &[&[0x2f][..], &bits.to_le_bytes()].concat());
+        assert_eq!(dimensions(&vp8l), Some((63, 47)));
+
+        // Extended: flags before 24-bit sizes less one
+        let vp8x = webp(b"VP8X", b"\0\0\0\0\x2b\x01\0\xc7\0\0");
+        assert_eq!(dimensions(&vp8x), Some((300, 200)));
+    }
+
+    #[test]
+    fn dimensions_jpeg() {
+        // An APP0 segment, padding, then a baseline frame header
+        let mut jpeg = b"\xff\xd8\xff\xe0\0\x10JFIF\0\x01\x01\0\0\x01\0\x01\0\0".to_vec();
+        jpeg.extend_from_slice(b"\xff\xff\xc0\0\x11\x08\x01\x68\x01\xe0");
+
+        assert_eq!(dimensions(&jpeg), Some((480, 360)));
+    }
+
+    #[test]
+    fn dimensions_unknown() {
+        assert_eq!(dimensions(b"<svg></svg>"), None);
+        assert_eq!(dimensions(b"GIF89a"), None);
+    }
+}
@@ -1,15 +1,38 @@
 use std::collections::HashMap;
+use std::time::SystemTime;
 
-use crate::entity::{Entity, FieldType, FieldData};
-use crate::schema::EntityDeclaration;
+use crate::conditional::ContentHasher;
+use crate::entity::{Entity, FieldType, FieldData, parse_date};
+use crate::schema::{Backlink, EntityDeclaration, FieldDeclaration};
+use crate::error::{ValidationError, ValidationErrorKind, ValidationReport};
+use crate::graphql;
+use crate::search::{SearchHit, SearchIndex};
 
 pub struct Cache {
     entities: HashMap<String, TypeGroup>,
+
+    /// IDs of the entities referring to each entity, by the referring type and
+    /// field, e.g. `backlinks[("Post", "author")]["veryjos"]`.
+    backlinks: HashMap<(String, String), HashMap<String, Vec<String>>>,
+
+    search: SearchIndex,
+
+    graphql_schema: graphql::Schema,
+
+    /// When the cache was created, which responses are last modified at.
+    loaded_at: SystemTime,
+
+    /// Hash of every schema and entity, which is the same across restarts
+    /// as long as the content is.
+    hash: String,
 }
 
 pub struct TypeGroup {
     pub declaration: EntityDeclaration,
     pub entities: HashMap<String, Entity>,
+
+    /// Entity IDs by the value of each unique field.
+    unique_indices: HashMap<String, HashMap<String, String>>,
 }
 
 impl TypeGroup {
@@ -17,53 +40,486 @@ impl TypeGroup {
         TypeGroup {
             declaration,
             entities: HashMap::new(),
+            unique_indices: HashMap::new(),
         }
     }
 
-    pub fn get_entity(&self, name: &str) -> &Entity {
-        self.entities.get(name).unwrap()
+    pub fn get_entity(&self, name: &str) -> Option<&Entity> {
+        self.entities.get(name)
+    }
+
+    /// Looks up the entity whose unique `field` has `value`.
+    ///
+    /// Returns `None` if the field isn't unique or `value` isn't a valid key
+    /// for it, or `Some(None)` if no entity has the value.
+    pub fn get_unique(&self, field: &str, value: &str) -> Option<Option<(&String, &Entity)>> {
+        let index = self.unique_indices.get(field)?;
+        let key = unique_key(&self.declaration.fields.get(field)?.ty, value)?;
+
+        Some(index.get(&key).and_then(|id| self.entities.get_key_value(id)))
+    }
+
+    /// Like [TypeGroup::get_unique], for a value already of the field's type.
+    pub fn get_unique_data(&self, field: &str, data: &FieldData) -> Option<Option<(&String, &Entity)>> {
+        let index = self.unique_indices.get(field)?;
+        let key = data_key(data)?;
+
+        Some(index.get(&key).and_then(|id| self.entities.get_key_value(id)))
     }
 
-    fn add_entity(&mut self, name: &str, ent: Entity) {
+    fn add_entity(&mut self, name: &str, mut ent: Entity) {
+        prepare_fields(&mut ent.fields, &self.declaration.fields);
+
+        for (field_name, _) in self.declaration.fields.iter().filter(|(_, f)| f.unique) {
+            let index = self.unique_indices.entry(field_name.to_owned()).or_default();
+
+            if let Some(key) = ent.fields.get(field_name).and_then(data_key) {
+                index.insert(key, name.to_owned());
+            }
+        }
+
         self.entities.insert(name.to_owned(), ent);
     }
 }
 
-pub fn validate_entity<'a>(cache: &'a Cache, decl: &'a EntityDeclaration, entity: &'a Entity) {
-    for (key, val) in entity.fields.iter() {
+/// Key a value is stored under in a unique index.
+fn data_key(data: &FieldData) -> Option<String> {
+    match data {
+        FieldData::Str(s) => Some(s.clone()),
+        FieldData::Num(n) => Some(n.to_string()),
+        FieldData::Date(d) => Some(d.to_string()),
+
+        _ => None
+    }
+}
+
+/// Key a value given as a string is looked up under in a unique index.
+fn unique_key(ty: &FieldType, value: &str) -> Option<String> {
+    match ty {
+        FieldType::Str | FieldType::Enum | FieldType::Ref(_) => Some(value.to_owned()),
+        FieldType::Num => value.parse::<f64>().ok().map(|n| n.to_string()),
+        FieldType::Date => parse_date(value).map(|d| d.to_string()),
+
+        _ => None
+    }
+}
+
+/// Coerces values to their declared types and fills in defaults for absent
+/// optional fields, recursing into objects.
+fn prepare_fields(fields: &mut HashMap<String, FieldData>, decls: &HashMap<String, FieldDeclaration>) {
+    for (field_name, data) in fields.iter_mut() {
+        if let Some(field) = decls.get(field_name) {
+            prepare_value(data, field, &field.ty);
+        }
+    }
+
+    for (field_name, field) in decls.iter() {
+        if let (false, Some(default)) = (field.required, &field.default) {
+            fields.entry(field_name.to_owned())
+                .or_insert_with(|| {
+                    let mut data = default.clone();
+                    prepare_value(&mut data, field, &field.ty);
+                    data
+                });
+        }
+    }
+}
+
+fn prepare_value(data: &mut FieldData, decl: &FieldDeclaration, ty: &FieldType) {
+    match (ty, data) {
+        (FieldType::List(item_ty), FieldData::List(items)) => {
+            for item in items.iter_mut() {
+                prepare_value(item, decl, item_ty);
+            }
+        },
+
+        (FieldType::Object, FieldData::Object(fields)) =>
+            prepare_fields(fields, &decl.fields),
+
+        (ty, data) => data.coerce(ty)
+    }
+}
+
+pub fn validate_entity<'a>(
+    cache: &'a Cache,
+    ty_name: &'a str,
+    ent_name: &'a str,
+    decl: &'a EntityDeclaration,
+    entity: &'a Entity,
+    report: &mut ValidationReport
+) {
+    let mut push = |field: &str, kind: ValidationErrorKind| report.push(ValidationError {
+        ty: ty_name.to_owned(),
+        entity: Some(ent_name.to_owned()),
+        field: field.to_owned(),
+        source: entity.source.clone(),
+        kind,
+    });
+
+    validate_fields(cache, "", &entity.fields, &decl.fields, &mut push);
+}
+
+/// Validates a set of fields against their declarations. Field names are
+/// reported relative to `prefix`, e.g. `seo.title` for nested objects.
+fn validate_fields(
+    cache: &Cache,
+    prefix: &str,
+    fields: &HashMap<String, FieldData>,
+    decls: &HashMap<String, FieldDeclaration>,
+    push: &mut dyn FnMut(&str, ValidationErrorKind)
+) {
+    let path = |key: &str| match prefix {
+        "" => key.to_owned(),
+        _ => format!("{prefix}.{key}")
+    };
+
+    // Validate all required fields are present
+    for (key, field) in decls.iter() {
+        let computed = matches!(field.ty, FieldType::Backlink);
+
+        if field.required && !computed && !fields.contains_key(key) {
+            push(&path(key), ValidationErrorKind::MissingField);
+        }
+    }
+
+    for (key, val) in fields.iter() {
         // Validate the field exists
-        let field = match decl.fields.get(key) {
+        let field = match decls.get(key) {
             Some(field) => field,
             None => {
-                println!(r#"No such field "{key}" on entity"#);
+                push(&path(key), ValidationErrorKind::UnknownField);
                 continue;
             }
         };
 
-        // Validate the type of the field
-        match (&field.ty, val) {
-            (FieldType::Str, FieldData::Str(_)) |
-            (FieldType::Bin, FieldData::Bin(_)) |
-            (FieldType::Num, FieldData::Num(_)) => {},
+        validate_value(cache, &path(key), field, &field.ty, val, push);
+        validate_constraints(&path(key), field, val, push);
+    }
+}
+
+/// Validates a value against the constraints of its declaration. Lengths apply
+/// to a list itself, while bounds and patterns apply to each of its items.
+fn validate_constraints(
+    field: &str,
+    decl: &FieldDeclaration,
+    val: &FieldData,
+    push: &mut dyn FnMut(&str, ValidationErrorKind)
+) {
+    let mut check_length = |len: usize| {
+        if let Some(min_length) = decl.min_length.filter(|min_length| len < *min_length) {
+            push(field, ValidationErrorKind::InvalidValue(
+                format!("length of {len} is less than the minimum of {min_length}")
+            ));
+        }
+
+        if let Some(max_length) = decl.max_length.filter(|max_length| len > *max_length) {
+            push(field, ValidationErrorKind::InvalidValue(
+                format!("length of {len} is more than the maximum of {max_length}")
+            ));
+        }
+    };
+
+    match val {
+        FieldData::Str(s) => check_length(s.chars().count()),
+        FieldData::List(items) => check_length(items.len()),
 
-            (FieldType::Ref(ty), FieldData::Str(ent_name)) => {
-                // Validate the reference entity type exists.
-                cache.entities.get(ty)
-                    .expect(&format!(r#"No such entity type "{:?}""#, ty));
+        _ => {}
+    }
+
+    let items = match val {
+        FieldData::List(items) => items.iter()
+            .enumerate()
+            .map(|(i, item)| (format!("{field}[{i}]"), item))
+            .collect(),
+
+        _ => vec![(field.to_owned(), val)]
+    };
 
-                // Validate the reference's entity exists.
-                cache.entities.get(ent_name)
-                    .expect(&format!(r#"No such entity "{}" of type "{:?}""#, ent_name, ty));
+    for (field, item) in items {
+        let field = field.as_str();
+
+        match item {
+            FieldData::Num(n) => {
+                if let Some(min) = decl.min.filter(|min| n < min) {
+                    push(field, ValidationErrorKind::InvalidValue(
+                        format!("{n} is less than the minimum of {min}")
+                    ));
+                }
+
+                if let Some(max) = decl.max.filter(|max| n > max) {
+                    push(field, ValidationErrorKind::InvalidValue(
+                        format!("{n} is more than the maximum of {max}")
+                    ));
+                }
             },
 
-            (_, _) => panic!(r#"Field "{}" declared as "{:?}", but "{:?}" was provided as value"#, key, field.ty, val)
+            FieldData::Str(s) => {
+                if let Some(pattern) = decl.pattern.as_ref().filter(|pattern| !pattern.0.is_match(s)) {
+                    push(field, ValidationErrorKind::InvalidValue(
+                        format!(r#""{s}" does not match the pattern "{pattern}""#)
+                    ));
+                }
+            },
+
+            _ => {}
+        }
+    }
+}
+
+/// Validates a value against its declared type, recursing into lists and objects.
+fn validate_value(
+    cache: &Cache,
+    field: &str,
+    decl: &FieldDeclaration,
+    ty: &FieldType,
+    val: &FieldData,
+    push: &mut dyn FnMut(&str, ValidationErrorKind)
+) {
+    match (ty, val) {
+        // Numbers are sorted and compared, which NaN can't be
+        (FieldType::Num, FieldData::Num(n)) if n.is_nan() =>
+            push(field, ValidationErrorKind::InvalidValue(
+                "NaN is not a valid num".to_owned()
+            )),
+
+        (FieldType::Str, FieldData::Str(_)) |
+        (FieldType::Bin, FieldData::Bin(_)) |
+        (FieldType::Bin, FieldData::File(_)) |
+        (FieldType::Num, FieldData::Num(_)) |
+        (FieldType::Bool, FieldData::Bool(_)) |
+        (FieldType::Date, FieldData::Date(_)) |
+        (FieldType::DateTime, FieldData::DateTime(_)) => {},
+
+        // Strings which failed to coerce to a date
+        (FieldType::Date, FieldData::Str(s)) |
+        (FieldType::DateTime, FieldData::Str(s)) =>
+            push(field, ValidationErrorKind::InvalidValue(
+                format!(r#""{s}" is not a valid {ty}"#)
+            )),
+
+        (FieldType::Enum, FieldData::Str(s)) => {
+            if !decl.values.contains(s) {
+                let values: Vec<_> = decl.values.iter()
+                    .map(|v| format!(r#""{v}""#))
+                    .collect();
+
+                push(field, ValidationErrorKind::InvalidValue(
+                    format!(r#""{s}" is not one of {}"#, values.join(", "))
+                ));
+            }
+        },
+
+        (FieldType::Ref(ref_ty), FieldData::Str(ref_name)) => {
+            // Unknown reference types are reported once against the schema
+            // by validate_declaration, so only check the entity exists.
+            let dangling = cache.entities.get(ref_ty)
+                .map_or(false, |group| !group.entities.contains_key(ref_name));
+
+            if dangling {
+                push(field, ValidationErrorKind::DanglingRef {
+                    ty: ref_ty.clone(),
+                    id: ref_name.clone(),
+                });
+            }
+        },
+
+        (FieldType::Backlink, _) =>
+            push(field, ValidationErrorKind::InvalidValue(
+                "backlinks are computed and can't be given a value".to_owned()
+            )),
+
+        (FieldType::List(item_ty), FieldData::List(items)) => {
+            for (i, item) in items.iter().enumerate() {
+                validate_value(cache, &format!("{field}[{i}]"), decl, item_ty, item, push);
+            }
+        },
+
+        (FieldType::Object, FieldData::Object(fields)) =>
+            validate_fields(cache, field, fields, &decl.fields, push),
+
+        (_, _) => push(field, ValidationErrorKind::TypeMismatch {
+            expected: ty.to_string(),
+            found: val.type_name().to_owned(),
+        })
+    };
+}
+
+pub fn validate_declaration<'a>(
+    cache: &'a Cache,
+    ty_name: &'a str,
+    decl: &'a EntityDeclaration,
+    report: &mut ValidationReport
+) {
+    let mut push = |field: &str, kind: ValidationErrorKind| report.push(ValidationError {
+        ty: ty_name.to_owned(),
+        entity: None,
+        field: field.to_owned(),
+        source: decl.source.clone(),
+        kind,
+    });
+
+    validate_field_declarations(cache, "", &decl.fields, &mut push);
+
+    for (key, field) in decl.fields.iter() {
+        let backlink = match (&field.ty, &field.backlink) {
+            (FieldType::Backlink, Some(backlink)) => backlink,
+
+            _ => continue
+        };
+
+        // Validate the backlink's field refers to this type
+        let referring = match cache.entities.get(&backlink.ty) {
+            Some(group) => group.declaration.fields.get(&backlink.field),
+            None => {
+                push(key, ValidationErrorKind::UnknownType(backlink.ty.clone()));
+                continue;
+            }
+        };
+
+        if referring.and_then(|f| f.ty.referenced_type()) != Some(ty_name) {
+            push(key, ValidationErrorKind::InvalidDeclaration(
+                format!(r#"backlink "{backlink}" doesn't refer to "{ty_name}""#)
+            ));
+        }
+    }
+}
+
+fn validate_field_declarations(
+    cache: &Cache,
+    prefix: &str,
+    decls: &HashMap<String, FieldDeclaration>,
+    push: &mut dyn FnMut(&str, ValidationErrorKind)
+) {
+    for (key, field) in decls.iter() {
+        let path = match prefix {
+            "" => key.to_owned(),
+            _ => format!("{prefix}.{key}")
         };
+
+        // Validate backlinks are top-level fields listing the field they follow
+        let backlink_error = match (&field.ty, &field.backlink) {
+            (FieldType::Backlink, None) => Some("field declared without a type or a backlink"),
+
+            (FieldType::Backlink, Some(_)) | (_, Some(_)) if !prefix.is_empty() =>
+                Some("backlinks can only be declared at the top level of an entity"),
+
+            (FieldType::Backlink, Some(_)) => None,
+            (_, Some(_)) => Some("backlink fields can't declare a type"),
+
+            _ => None
+        };
+
+        if let Some(message) = backlink_error {
+            push(&path, ValidationErrorKind::InvalidDeclaration(message.to_owned()));
+        }
+
+        // Validate defaults are only given to optional fields, and are valid
+        // values of the field regardless of the entities using them
+        if field.required && field.default.is_some() {
+            push(&path, ValidationErrorKind::InvalidDeclaration(
+                "required fields can't declare a default".to_owned()
+            ));
+        }
+
+        if let Some(default) = &field.default {
+            let mut data = default.clone();
+            prepare_value(&mut data, field, &field.ty);
+
+            let mut push_default = |_: &str, kind: ValidationErrorKind| push(&path, ValidationErrorKind::InvalidDeclaration(
+                format!("invalid default: {kind}")
+            ));
+
+            validate_value(cache, &path, field, &field.ty, &data, &mut push_default);
+            validate_constraints(&path, field, &data, &mut push_default);
+        }
+
+        // Validate referenced entity types exist
+        if let Some(ty) = field.ty.referenced_type() {
+            if !cache.entities.contains_key(ty) {
+                push(&path, ValidationErrorKind::UnknownType(ty.to_owned()));
+            }
+        }
+
+        // Validate constraints apply to the type of the field
+        let constraints = [
+            ("min", field.min.is_some(), matches!(field.ty.item_type(), FieldType::Num)),
+            ("max", field.max.is_some(), matches!(field.ty.item_type(), FieldType::Num)),
+            ("min_length", field.min_length.is_some(), matches!(field.ty, FieldType::Str | FieldType::List(_))),
+            ("max_length", field.max_length.is_some(), matches!(field.ty, FieldType::Str | FieldType::List(_))),
+            ("pattern", field.pattern.is_some(), matches!(field.ty.item_type(), FieldType::Str)),
+            ("unique", field.unique, prefix.is_empty() && matches!(field.ty,
+                FieldType::Str | FieldType::Num | FieldType::Date | FieldType::Enum | FieldType::Ref(_))),
+            ("searchable", field.searchable, prefix.is_empty() && matches!(field.ty.item_type(), FieldType::Str)),
+        ];
+
+        for (name, _, _) in constraints.iter().filter(|(_, set, applies)| *set && !applies) {
+            push(&path, ValidationErrorKind::InvalidDeclaration(
+                format!(r#"constraint "{name}" can't be applied to a "{}" field"#, field.ty)
+            ));
+        }
+
+        match (field.ty.item_type(), field.values.is_empty(), field.fields.is_empty()) {
+            // Validate enums declare their values
+            (FieldType::Enum, true, _) =>
+                push(&path, ValidationErrorKind::InvalidDeclaration(
+                    "enum declared without any values".to_owned()
+                )),
+
+            // Validate objects declare their fields
+            (FieldType::Object, _, true) =>
+                push(&path, ValidationErrorKind::InvalidDeclaration(
+                    "object declared without any fields".to_owned()
+                )),
+
+            (FieldType::Object, _, false) =>
+                validate_field_declarations(cache, &path, &field.fields, push),
+
+            _ => {}
+        }
     }
 }
 
-pub fn validate_type_group<'a>(cache: &'a Cache, type_group: &'a TypeGroup) {
-    for (_, entity) in type_group.entities.iter() {
-        validate_entity(cache, &type_group.declaration, &entity);
+pub fn validate_type_group<'a>(
+    cache: &'a Cache,
+    ty_name: &'a str,
+    type_group: &'a TypeGroup,
+    report: &mut ValidationReport
+) {
+    // Sort the entities so duplicates are reported against the same entity on every load
+    let mut entities: Vec<_> = type_group.entities.iter().collect();
+    entities.sort_unstable_by_key(|(ent_name, _)| *ent_name);
+
+    for (ent_name, entity) in entities.iter() {
+        validate_entity(cache, ty_name, ent_name, &type_group.declaration, &entity, report);
+    }
+
+    // Validate unique fields have no duplicate values
+    for (key, _) in type_group.declaration.fields.iter().filter(|(_, f)| f.unique) {
+        let mut seen: HashMap<String, &str> = HashMap::new();
+
+        for (ent_name, entity) in entities.iter() {
+            let value = match entity.fields.get(key).and_then(data_key) {
+                Some(value) => value,
+                None => continue
+            };
+
+            match seen.get(&value) {
+                Some(other) => report.push(ValidationError {
+                    ty: ty_name.to_owned(),
+                    entity: Some(ent_name.to_string()),
+                    field: key.to_owned(),
+                    source: entity.source.clone(),
+                    kind: ValidationErrorKind::Duplicate {
+                        value,
+                        other: other.to_string(),
+                    },
+                }),
+
+                None => {
+                    seen.insert(value, ent_name);
+                }
+            }
+        }
     }
 }
 
@@ -71,16 +527,204 @@ impl Cache {
     pub fn new() -> Cache {
         Cache {
             entities: HashMap::new(),
+            backlinks: HashMap::new(),
+            search: SearchIndex::default(),
+            graphql_schema: graphql::Schema::default(),
+            loaded_at: SystemTime::now(),
+            hash: String::new(),
         }
     }
 
-    pub fn validated(self) -> Self {
+    /// Hashes every entity, the fields served on their own and the whole
+    /// cache, so that conditional requests are answered without hashing
+    /// content per request.
+    pub fn index_hashes(&mut self) {
+        for group in self.entities.values_mut() {
+            for ent in group.entities.values_mut() {
+                let mut names: Vec<&String> = ent.fields.keys().collect();
+                names.sort();
+
+                let mut hasher = ContentHasher::default();
+                let mut field_hashes = HashMap::new();
+
+                for name in names {
+                    let data = &ent.fields[name];
+                    let hash = data.hash();
+
+                    hasher.update(name.as_bytes());
+                    hasher.update(hash.as_bytes());
+
+                    if let FieldData::List(items) = data {
+                        for (i, item) in items.iter().enumerate() {
+                            field_hashes.insert(format!("{name}/{i}"), item.hash());
+                        }
+                    }
+
+                    field_hashes.insert(name.clone(), hash);
+                }
+
+                // Fields are served with a type chosen by their file extension
+                for (key, hash) in field_hashes.iter_mut() {
+                    if let Some(extension) = ent.extensions.get(key) {
+                        let mut hasher = ContentHasher::default();
+                        hasher.update(hash.as_bytes());
+                        hasher.update(extension.as_bytes());
+
+                        *hash = hasher.finish();
+                    }
+                }
+
+                ent.hash = hasher.finish();
+                ent.field_hashes = field_hashes;
+            }
+        }
+
+        let mut ty_names: Vec<&String> = self.entities.keys().collect();
+        ty_names.sort();
+
+        let mut hasher = ContentHasher::default();
+
+        for ty_name in ty_names {
+            let group = &self.entities[ty_name];
+
+            hasher.update(ty_name.as_bytes());
+            hasher.update(group.declaration.hash.as_bytes());
+
+            let mut ent_names: Vec<&String> = group.entities.keys().collect();
+            ent_names.sort();
+
+            for ent_name in ent_names {
+                hasher.update(ent_name.as_bytes());
+                hasher.update(group.entities[ent_name].hash.as_bytes());
+            }
+        }
+
+        self.hash = hasher.finish();
+    }
+
+    /// Indexes the references between entities, so the entities referring to
+    /// an entity can be found without scanning. Must be called once every
+    /// entity has been added.
+    pub fn index_backlinks(&mut self) {
+        let mut backlinks: HashMap<_, HashMap<String, Vec<String>>> = HashMap::new();
+
+        for (ty_name, group) in self.entities.iter() {
+            for (key, field) in group.declaration.fields.iter() {
+                if field.ty.referenced_type().is_none() {
+                    continue;
+                }
+
+                let index = backlinks.entry((ty_name.to_owned(), key.to_owned())).or_default();
+
+                for (ent_name, entity) in group.entities.iter() {
+                    let refs = match entity.fields.get(key) {
+                        Some(FieldData::List(items)) => items.iter().collect(),
+                        Some(data) => vec![data],
+                        None => continue
+                    };
+
+                    for data in refs {
+                        if let FieldData::Str(ref_name) = data {
+                            index.entry(ref_name.to_owned()).or_default().push(ent_name.to_owned());
+                        }
+                    }
+                }
+            }
+        }
+
+        // Keep the order stable between reloads
+        for ids in backlinks.values_mut().flat_map(|index| index.values_mut()) {
+            ids.sort_unstable();
+            ids.dedup();
+        }
+
+        self.backlinks = backlinks;
+    }
+
+    /// Indexes the text of every searchable field for [Cache::search]. Must be
+    /// called once every entity has been added.
+    pub fn index_search(&mut self) {
+        let mut search = SearchIndex::default();
+
+        for (ty_name, group) in self.entities.iter() {
+            let fields: Vec<&String> = group.declaration.fields.iter()
+                .filter(|(_, field)| field.searchable)
+                .map(|(key, _)| key)
+                .collect();
+
+            if fields.is_empty() {
+                continue;
+            }
+
+            for (ent_name, entity) in group.entities.iter() {
+                let texts = fields.iter()
+                    .filter_map(|key| entity.fields.get(*key))
+                    .flat_map(|data| match data {
+                        FieldData::List(items) => items.iter().collect(),
+                        _ => vec![data]
+                    })
+                    .filter_map(|data| match data {
+                        FieldData::Str(s) => Some(s.as_str()),
+                        _ => None
+                    });
+
+                search.add_document(ty_name, ent_name, texts);
+            }
+        }
+
+        self.search = search;
+    }
+
+    /// Entities whose searchable fields match `query`, most relevant first.
+    pub fn search(&self, query: &str, ty: Option<&str>) -> Vec<SearchHit<'_>> {
+        self.search.search(query, ty)
+    }
+
+    /// Generates the GraphQL schema for the declared types. Must be called once
+    /// every type has been added.
+    pub fn generate_graphql_schema(&mut self) {
+        self.graphql_schema = graphql::Schema::generate(self);
+    }
+
+    pub fn graphql_schema(&self) -> &graphql::Schema {
+        &self.graphql_schema
+    }
+
+    pub fn loaded_at(&self) -> SystemTime {
+        self.loaded_at
+    }
+
+    pub fn hash(&self) -> &str {
+        &self.hash
+    }
+
+    /// IDs of the entities referring to `ent_name` through `backlink`.
+    pub fn get_backlinks(&self, backlink: &Backlink, ent_name: &str) -> &[String] {
+        self.backlinks.get(&(backlink.ty.clone(), backlink.field.clone()))
+            .and_then(|index| index.get(ent_name))
+            .map_or(&[], |ids| ids.as_slice())
+    }
+
+    /// Validates every entity in the cache, returning the cache if no problems
+    /// were found or a [ValidationReport] listing all of them otherwise.
+    pub fn validated(self) -> Result<Self, ValidationReport> {
+        let mut report = ValidationReport::default();
+
+        // Validate the schemas before their entities
+        for (ty_name, group) in self.entities.iter() {
+            validate_declaration(&self, ty_name, &group.declaration, &mut report);
+        }
+
         // Validate each type group
-        for (_, group) in self.entities.iter() {
-            validate_type_group(&self, group);
+        for (ty_name, group) in self.entities.iter() {
+            validate_type_group(&self, ty_name, group, &mut report);
         }
 
-        self
+        if report.is_empty() {
+            Ok(self)
+        } else {
+            Err(report)
+        }
     }
 
     pub fn add_type(&mut self, name: &str, decl: EntityDeclaration) {
@@ -93,7 +737,11 @@ impl Cache {
         group.add_entity(name, ent);
     }
 
-    pub fn get_group(&self, type_name: &str) -> &TypeGroup {
-        self.entities.get(type_name).unwrap()
+    pub fn groups(&self) -> impl Iterator<Item = (&String, &TypeGroup)> {
+        self.entities.iter()
+    }
+
+    pub fn get_group(&self, type_name: &str) -> Option<&TypeGroup> {
+        self.entities.get(type_name)
     }
 }
@@ -15,6 +15,10 @@ pub struct CliArgs {
     /// Binding port.
     #[clap(short, long, default_value = "8080")]
     pub port: u16,
+
+    /// Size in bytes above which binary files are streamed from disk.
+    #[clap(long, default_value = "1048576")]
+    pub stream_threshold: u64,
 }
 
 impl CliArgs {
@@ -0,0 +1,194 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chrono::{DateTime, Utc};
+
+/// Format of dates in HTTP headers, e.g. `Wed, 21 Oct 2015 07:28:00 GMT`.
+const HTTP_DATE_FORMAT: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+/// Hash of some content as a hex string, used as its entity tag.
+pub fn content_hash(bytes: &[u8]) -> String {
+    let mut hasher = ContentHasher::default();
+    hasher.update(bytes);
+
+    hasher.finish()
+}
+
+/// Computes a [content_hash] of content read in chunks.
+pub struct ContentHasher(u64);
+
+impl Default for ContentHasher {
+    fn default() -> Self {
+        ContentHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl ContentHasher {
+    // 64-bit FNV-1a, which is fast and the same across builds and restarts
+    pub fn update(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    pub fn finish(&self) -> String {
+        format!("{:016x}", self.0)
+    }
+}
+
+/// Seconds since the Unix epoch, the precision of HTTP dates.
+fn seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+pub fn http_date(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time).format(HTTP_DATE_FORMAT).to_string()
+}
+
+pub fn parse_http_date(s: &str) -> Option<SystemTime> {
+    DateTime::parse_from_rfc2822(s).ok().map(SystemTime::from)
+}
+
+/// Whether the copy a client holds, as described by its `If-None-Match` and
+/// `If-Modified-Since` headers, is still fresh. Tags are compared weakly, and
+/// `If-Modified-Since` is ignored if `If-None-Match` is sent.
+pub fn is_fresh(
+    if_none_match: Option<&str>,
+    if_modified_since: Option<&str>,
+    etag: &str,
+    last_modified: SystemTime
+) -> bool {
+    if let Some(tags) = if_none_match {
+        return tags.split(',')
+            .map(|tag| tag.trim())
+            .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag);
+    }
+
+    match if_modified_since.and_then(parse_http_date) {
+        Some(since) => seconds(last_modified) <= seconds(since),
+        None => false
+    }
+}
+
+/// How to answer a `Range` header.
+#[derive(Debug, PartialEq)]
+pub enum ByteRange {
+    /// Send the bytes from `start` up to but excluding `end`.
+    Partial(u64, u64),
+
+    /// None of the requested bytes exist.
+    Unsatisfiable,
+}
+
+/// Parses a `Range` header for content of `len` bytes, or `None` if the whole
+/// content should be sent instead. Requests for several ranges would need a
+/// multipart response, so they're answered with the whole content too.
+pub fn parse_range(header: &str, len: u64) -> Option<ByteRange> {
+    let spec = header.trim().strip_prefix("bytes=")?;
+
+    if spec.contains(',') {
+        return None;
+    }
+
+    let mut bounds = spec.splitn(2, '-');
+    let first = bounds.next()?.trim();
+    let last = bounds.next()?.trim();
+
+    let (start, end) = match (first.parse::<u64>().ok(), last.parse::<u64>().ok()) {
+        // The last `n` bytes, e.g. "bytes=-500"
+        (None, Some(n)) if first.is_empty() => (len.saturating_sub(n), len),
+
+        // Everything from `start`, e.g. "bytes=500-"
+        (Some(start), None) if last.is_empty() => (start, len),
+
+        (Some(start), Some(last)) if start <= last => (start, last.saturating_add(1).min(len)),
+
+        _ => return None
+    };
+
+    match start < end {
+        true => Some(ByteRange::Partial(start, end)),
+        false => Some(ByteRange::Unsatisfiable)
+    }
+}
+
+/// Whether a `Range` should be honored given the request's `If-Range`, which
+/// holds the entity tag or modification date of the client's partial copy.
+/// Entity tags are compared strongly, as partial copies must match exactly.
+pub fn if_range_matches(if_range: Option<&str>, etag: &str, last_modified: SystemTime) -> bool {
+    let if_range = match if_range {
+        Some(if_range) => if_range.trim(),
+        None => return true
+    };
+
+    if if_range.starts_with('"') || if_range.starts_with("W/") {
+        return if_range == etag;
+    }
+
+    parse_http_date(if_range).map_or(false, |date| seconds(date) == seconds(last_modified))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    /// `Wed, 21 Oct 2015 07:28:00 GMT`
+    fn modified() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_445_412_480)
+    }
+
+    #[test]
+    fn parse_range_bounded() {
+        assert_eq!(parse_range("bytes=0-4", 10), Some(ByteRange::Partial(0, 5)));
+        assert_eq!(parse_range("bytes=5-", 10), Some(ByteRange::Partial(5, 10)));
+        assert_eq!(parse_range("bytes=8-100", 10), Some(ByteRange::Partial(8, 10)));
+    }
+
+    #[test]
+    fn parse_range_suffix() {
+        assert_eq!(parse_range("bytes=-3", 10), Some(ByteRange::Partial(7, 10)));
+        assert_eq!(parse_range("bytes=-100", 10), Some(ByteRange::Partial(0, 10)));
+        assert_eq!(parse_range("bytes=-0", 10), Some(ByteRange::Unsatisfiable));
+    }
+
+    #[test]
+    fn parse_range_beyond_length() {
+        assert_eq!(parse_range("bytes=10-", 10), Some(ByteRange::Unsatisfiable));
+        assert_eq!(parse_range("bytes=20-30", 10), Some(ByteRange::Unsatisfiable));
+    }
+
+    #[test]
+    fn parse_range_ignored() {
+        // Reversed bounds, several ranges, other units and malformed specs
+        assert_eq!(parse_range("bytes=5-3", 10), None);
+        assert_eq!(parse_range("bytes=0-1,4-5", 10), None);
+        assert_eq!(parse_range("items=0-1", 10), None);
+        assert_eq!(parse_range("bytes=abc", 10), None);
+        assert_eq!(parse_range("bytes=-", 10), None);
+    }
+
+    #[test]
+    fn parse_range_empty_content() {
+        assert_eq!(parse_range("bytes=0-", 0), Some(ByteRange::Unsatisfiable));
+        assert_eq!(parse_range("bytes=0-0", 0), Some(ByteRange::Unsatisfiable));
+        assert_eq!(parse_range("bytes=-5", 0), Some(ByteRange::Unsatisfiable));
+    }
+
+    #[test]
+    fn if_range_etag() {
+        assert!(if_range_matches(None, r#""a""#, modified()));
+        assert!(if_range_matches(Some(r#""a""#), r#""a""#, modified()));
+        assert!(!if_range_matches(Some(r#""b""#), r#""a""#, modified()));
+
+        // Weak tags never match, as partial copies must be identical
+        assert!(!if_range_matches(Some(r#"W/"a""#), r#""a""#, modified()));
+    }
+
+    #[test]
+    fn if_range_date() {
+        assert!(if_range_matches(Some("Wed, 21 Oct 2015 07:28:00 GMT"), r#""a""#, modified()));
+        assert!(!if_range_matches(Some("Wed, 21 Oct 2015 07:28:01 GMT"), r#""a""#, modified()));
+        assert!(!if_range_matches(Some("garbage"), r#""a""#, modified()));
+    }
+}
@@ -1,25 +1,307 @@
+use std::cmp::Ordering;
 use std::fmt;
 use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::path::PathBuf;
+use std::time::SystemTime;
 
 use std::str::FromStr;
 
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, TimeZone};
 use serde::de;
-use serde::{Serialize, Deserialize, Deserializer};
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+
+use crate::conditional::{content_hash, ContentHasher};
 
 #[derive(Debug)]
 pub enum FieldType {
     Str,
     Bin,
     Num,
+    Bool,
+    Date,
+    DateTime,
+    Enum,
+    Object,
+    Backlink,
+
+    Ref(String),
+    List(Box<FieldType>)
+}
+
+impl FieldType {
+    /// Type of the items of a list, or the type itself otherwise.
+    pub fn item_type(&self) -> &FieldType {
+        match self {
+            FieldType::List(item_ty) => item_ty.item_type(),
+
+            _ => self
+        }
+    }
+
+    /// Name of the entity type referenced by this field, if any.
+    pub fn referenced_type(&self) -> Option<&str> {
+        match self.item_type() {
+            FieldType::Ref(ty) => Some(ty),
+
+            _ => None
+        }
+    }
+}
+
+impl From<&str> for FieldType {
+    fn from(s: &str) -> FieldType {
+        // Lists are declared by wrapping the item type in brackets, e.g. "[str]"
+        if let Some(item_ty) = s.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            return FieldType::List(Box::new(item_ty.trim().into()));
+        }
 
-    Ref(String)
+        match s {
+            "string" | "str" => FieldType::Str,
+            "binary" | "bin" => FieldType::Bin,
+            "number" | "num" => FieldType::Num,
+            "boolean" | "bool" => FieldType::Bool,
+            "date" => FieldType::Date,
+            "datetime" => FieldType::DateTime,
+            "enum" => FieldType::Enum,
+            "object" => FieldType::Object,
+            "backlink" => FieldType::Backlink,
+
+            _ => FieldType::Ref(s.to_owned())
+        }
+    }
+}
+
+/// Name of the field toml uses to smuggle native datetimes through serde.
+const TOML_DATETIME_FIELD: &str = "$__toml_private_datetime";
+
+/// Parses an ISO-8601 date, such as `2020-05-27`.
+pub fn parse_date(s: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d").ok()
+}
+
+/// Parses an ISO-8601 datetime. Datetimes without an offset, and plain dates,
+/// are taken to be in UTC.
+pub fn parse_datetime(s: &str) -> Option<DateTime<FixedOffset>> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+        return Some(dt);
+    }
+
+    let naive = NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f").ok()
+        .or_else(|| parse_date(s).and_then(|d| d.and_hms_opt(0, 0, 0)))?;
+
+    FixedOffset::east_opt(0)?
+        .from_local_datetime(&naive)
+        .single()
 }
 
-#[derive(Debug, Serialize)]
+impl fmt::Display for FieldType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FieldType::Str => write!(f, "str"),
+            FieldType::Bin => write!(f, "bin"),
+            FieldType::Num => write!(f, "num"),
+            FieldType::Bool => write!(f, "bool"),
+            FieldType::Date => write!(f, "date"),
+            FieldType::DateTime => write!(f, "datetime"),
+            FieldType::Enum => write!(f, "enum"),
+            FieldType::Object => write!(f, "object"),
+            FieldType::Backlink => write!(f, "backlink"),
+
+            FieldType::Ref(ty) => write!(f, "{ty}"),
+            FieldType::List(item_ty) => write!(f, "[{item_ty}]")
+        }
+    }
+}
+
+impl Serialize for FieldType {
+    fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer
+    {
+        ser.collect_str(self)
+    }
+}
+
+/// Binary content left on disk and streamed when requested, for files too
+/// large to hold in memory.
+#[derive(Clone, Debug, Serialize)]
+pub struct BinFile {
+    #[serde(skip)]
+    pub path: PathBuf,
+
+    pub size: u64,
+
+    /// Hash of the content, as computed by [crate::conditional::content_hash].
+    pub hash: String,
+
+    /// Media type recognized from the file's magic bytes, if any.
+    #[serde(skip)]
+    pub mime: Option<&'static str>,
+
+    /// Width and height, if the file is an image.
+    #[serde(skip)]
+    pub dimensions: Option<(u32, u32)>,
+
+    /// Modification time of the file when it was loaded.
+    #[serde(skip)]
+    pub modified: Option<SystemTime>,
+}
+
+impl BinFile {
+    /// Opens the file, failing if it changed since it was loaded, as it would
+    /// no longer match its recorded size and hash.
+    pub fn open(&self) -> io::Result<File> {
+        let file = File::open(&self.path)?;
+        let metadata = file.metadata()?;
+
+        if metadata.len() != self.size || metadata.modified().ok() != self.modified {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("{} changed since it was loaded", self.path.display())
+            ));
+        }
+
+        Ok(file)
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(untagged)]
 pub enum FieldData {
     Str(String),
     Bin(Vec<u8>),
-    Num(f64)
+    File(BinFile),
+    Num(f64),
+    Bool(bool),
+    Date(NaiveDate),
+    DateTime(DateTime<FixedOffset>),
+    List(Vec<FieldData>),
+    Object(HashMap<String, FieldData>)
+}
+
+impl FieldData {
+    /// Hash of the value, which for text and binary data is the hash of the
+    /// content served by the field routes.
+    pub fn hash(&self) -> String {
+        match self {
+            FieldData::Str(s) => content_hash(s.as_bytes()),
+            FieldData::Bin(bytes) => content_hash(bytes),
+            FieldData::File(file) => file.hash.clone(),
+
+            _ => {
+                let mut hasher = ContentHasher::default();
+                self.hash_into(&mut hasher);
+
+                hasher.finish()
+            }
+        }
+    }
+
+    /// Feeds the value to `hasher`, tagged with its kind and with the lengths
+    /// of variable-sized parts so that different values can't run together.
+    /// Object fields are fed in order of their names.
+    fn hash_into(&self, hasher: &mut ContentHasher) {
+        fn update_len(hasher: &mut ContentHasher, bytes: &[u8]) {
+            hasher.update(&(bytes.len() as u64).to_le_bytes());
+            hasher.update(bytes);
+        }
+
+        match self {
+            FieldData::Str(s) => {
+                hasher.update(b"s");
+                update_len(hasher, s.as_bytes());
+            },
+            FieldData::Bin(bytes) => {
+                hasher.update(b"b");
+                update_len(hasher, bytes);
+            },
+            FieldData::File(file) => {
+                hasher.update(b"b");
+                update_len(hasher, file.hash.as_bytes());
+            },
+            FieldData::Num(n) => {
+                hasher.update(b"n");
+                hasher.update(&n.to_bits().to_le_bytes());
+            },
+            FieldData::Bool(b) => hasher.update(if *b { b"t" } else { b"f" }),
+            FieldData::Date(d) => {
+                hasher.update(b"d");
+                update_len(hasher, d.to_string().as_bytes());
+            },
+            FieldData::DateTime(dt) => {
+                hasher.update(b"D");
+                update_len(hasher, dt.to_rfc3339().as_bytes());
+            },
+            FieldData::List(items) => {
+                hasher.update(b"l");
+                hasher.update(&(items.len() as u64).to_le_bytes());
+
+                for item in items {
+                    item.hash_into(hasher);
+                }
+            },
+            FieldData::Object(fields) => {
+                let mut names: Vec<&String> = fields.keys().collect();
+                names.sort();
+
+                hasher.update(b"o");
+                hasher.update(&(names.len() as u64).to_le_bytes());
+
+                for name in names {
+                    update_len(hasher, name.as_bytes());
+                    fields[name].hash_into(hasher);
+                }
+            }
+        }
+    }
+
+    /// Name of the kind of value held, as used in schema declarations.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            FieldData::Str(_) => "str",
+            FieldData::Bin(_) | FieldData::File(_) => "bin",
+            FieldData::Num(_) => "num",
+            FieldData::Bool(_) => "bool",
+            FieldData::Date(_) => "date",
+            FieldData::DateTime(_) => "datetime",
+            FieldData::List(_) => "list",
+            FieldData::Object(_) => "object"
+        }
+    }
+
+    /// Converts the value to the representation of the declared type where
+    /// possible, e.g. ISO-8601 strings in fields declared as dates.
+    pub fn coerce(&mut self, ty: &FieldType) {
+        let coerced = match (ty, &*self) {
+            (FieldType::Date, FieldData::Str(s)) =>
+                parse_date(s).map(FieldData::Date),
+            (FieldType::DateTime, FieldData::Str(s)) =>
+                parse_datetime(s).map(FieldData::DateTime),
+            (FieldType::DateTime, FieldData::Date(d)) =>
+                parse_datetime(&d.to_string()).map(FieldData::DateTime),
+
+            _ => None
+        };
+
+        if let Some(coerced) = coerced {
+            *self = coerced;
+        }
+    }
+
+    /// Orders two values of the same type, or `None` if they can't be compared.
+    pub fn compare(&self, other: &FieldData) -> Option<Ordering> {
+        match (self, other) {
+            (FieldData::Str(lhs), FieldData::Str(rhs)) => lhs.partial_cmp(rhs),
+            (FieldData::Num(lhs), FieldData::Num(rhs)) => lhs.partial_cmp(rhs),
+            (FieldData::Bool(lhs), FieldData::Bool(rhs)) => lhs.partial_cmp(rhs),
+            (FieldData::Date(lhs), FieldData::Date(rhs)) => lhs.partial_cmp(rhs),
+            (FieldData::DateTime(lhs), FieldData::DateTime(rhs)) => lhs.partial_cmp(rhs),
+
+            _ => None
+        }
+    }
 }
 
 impl<'de> Deserialize<'de> for FieldData {
@@ -56,6 +338,57 @@ impl<'de> Deserialize<'de> for FieldData {
             {
                 self.visit_f64(v as f64)
             }
+
+            fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E>
+            where
+                E: de::Error
+            {
+                Ok(FieldData::Bool(v))
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: de::SeqAccess<'de>
+            {
+                let mut items = Vec::new();
+
+                while let Some(item) = seq.next_element()? {
+                    items.push(item);
+                }
+
+                Ok(FieldData::List(items))
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: de::MapAccess<'de>
+            {
+                let mut fields = HashMap::new();
+
+                match map.next_key::<String>()? {
+                    // Native TOML dates and datetimes
+                    Some(ref key) if key == TOML_DATETIME_FIELD => {
+                        let s: String = map.next_value()?;
+
+                        return parse_date(&s).map(FieldData::Date)
+                            .or_else(|| parse_datetime(&s).map(FieldData::DateTime))
+                            .ok_or_else(|| de::Error::custom(format!(r#"invalid date or datetime "{s}""#)));
+                    },
+
+                    Some(key) => {
+                        fields.insert(key, map.next_value()?);
+                    },
+
+                    None => {}
+                }
+
+                // Inline tables
+                while let Some((key, value)) = map.next_entry()? {
+                    fields.insert(key, value);
+                }
+
+                Ok(FieldData::Object(fields))
+            }
         }
 
         deser.deserialize_any(FieldDataVisitor {})
@@ -80,13 +413,7 @@ impl<'de> Deserialize<'de> for FieldType {
             where
                 E: de::Error
             {
-                Ok(match s {
-                    "string" | "str" => FieldType::Str,
-                    "binary" | "bin" => FieldType::Bin,
-                    "number" | "num" => FieldType::Num,
-
-                    _ => FieldType::Ref(s.to_owned())
-                })
+                Ok(s.into())
             }
         }
 
@@ -96,10 +423,46 @@ impl<'de> Deserialize<'de> for FieldType {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Entity {
+    /// Path the entity was loaded from.
+    #[serde(skip)]
+    pub source: PathBuf,
+
+    /// Extensions of the files fields were read from, by field name, or by
+    /// `field/index` for the items of list fields read from folders.
+    #[serde(skip)]
+    pub extensions: HashMap<String, String>,
+
+    /// Hash of every field, computed when the content is loaded.
+    #[serde(skip)]
+    pub hash: String,
+
+    /// Hashes of the fields served on their own, keyed like `extensions`.
+    #[serde(skip)]
+    pub field_hashes: HashMap<String, String>,
+
     #[serde(flatten)]
     pub fields: HashMap<String, FieldData>,
 }
 
+impl Entity {
+    /// Gets the value of the field at `path`, descending into objects.
+    pub fn get_path(&self, path: &[&str]) -> Option<&FieldData> {
+        find_value(&self.fields, path)
+    }
+}
+
+fn find_value<'e>(fields: &'e HashMap<String, FieldData>, path: &[&str]) -> Option<&'e FieldData> {
+    let (first, rest) = path.split_first()?;
+    let data = fields.get(*first)?;
+
+    match (data, rest.is_empty()) {
+        (_, true) => Some(data),
+        (FieldData::Object(fields), false) => find_value(fields, rest),
+
+        _ => None
+    }
+}
+
 impl FromStr for Entity {
     type Err = toml::de::Error;
 
@@ -1,5 +1,6 @@
 use std::error::Error;
 use std::fmt;
+use std::path::PathBuf;
 
 #[derive(Debug)]
 pub struct StringError {
@@ -25,3 +26,161 @@ impl fmt::Display for StringError {
         write!(f, "{}", self.data)
     }
 }
+
+/// A single problem found while validating the contents of a [Cache](crate::cache::Cache).
+#[derive(Debug)]
+pub struct ValidationError {
+    pub ty: String,
+    /// Entity the problem was found in, or `None` for problems in the schema itself.
+    pub entity: Option<String>,
+    pub field: String,
+    pub source: PathBuf,
+    pub kind: ValidationErrorKind,
+}
+
+#[derive(Debug)]
+pub enum ValidationErrorKind {
+    UnknownField,
+    MissingField,
+    TypeMismatch { expected: String, found: String },
+    InvalidValue(String),
+    Duplicate { value: String, other: String },
+    UnknownType(String),
+    InvalidDeclaration(String),
+    DanglingRef { ty: String, id: String },
+}
+
+impl fmt::Display for ValidationErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationErrorKind::UnknownField =>
+                write!(f, "no such field in schema"),
+            ValidationErrorKind::MissingField =>
+                write!(f, "required field is missing"),
+            ValidationErrorKind::TypeMismatch { expected, found } =>
+                write!(f, r#"declared as "{expected}", but "{found}" was provided as value"#),
+            ValidationErrorKind::InvalidValue(message) =>
+                write!(f, "{message}"),
+            ValidationErrorKind::Duplicate { value, other } =>
+                write!(f, r#"unique value "{value}" is already used by "{other}""#),
+            ValidationErrorKind::UnknownType(ty) =>
+                write!(f, r#"no such entity type "{ty}""#),
+            ValidationErrorKind::InvalidDeclaration(message) =>
+                write!(f, "{message}"),
+            ValidationErrorKind::DanglingRef { ty, id } =>
+                write!(f, r#"no such entity "{id}" of type "{ty}""#),
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.entity {
+            Some(entity) =>
+                write!(f, "{}: {}/{}.{}: {}", self.source.display(), self.ty, entity, self.field, self.kind),
+            None =>
+                write!(f, "{}: {}.{}: {}", self.source.display(), self.ty, self.field, self.kind),
+        }
+    }
+}
+
+/// Every [ValidationError] found across all type groups of a cache.
+#[derive(Debug, Default)]
+pub struct ValidationReport {
+    pub errors: Vec<ValidationError>,
+}
+
+impl ValidationReport {
+    pub fn push(&mut self, error: ValidationError) {
+        self.errors.push(error);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+impl Error for ValidationReport {}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Found {} validation error(s):", self.errors.len())?;
+
+        for error in &self.errors {
+            writeln!(f, "  {error}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A query which can't be evaluated against the schema of the cache.
+#[derive(Debug)]
+pub enum QueryError {
+    UnknownType(String),
+    UnknownField(String),
+    InvalidFilter(String),
+    InvalidSort(String),
+    InvalidPage(String),
+    InvalidAggregate(String),
+}
+
+impl Error for QueryError {}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            QueryError::UnknownType(ty) =>
+                write!(f, r#"no such type "{ty}""#),
+            QueryError::UnknownField(field) =>
+                write!(f, r#"no such field "{field}""#),
+            QueryError::InvalidFilter(message) =>
+                write!(f, "invalid filter: {message}"),
+            QueryError::InvalidSort(message) =>
+                write!(f, "invalid sort: {message}"),
+            QueryError::InvalidPage(message) =>
+                write!(f, "invalid page: {message}"),
+            QueryError::InvalidAggregate(message) =>
+                write!(f, "invalid aggregate: {message}"),
+        }
+    }
+}
+
+/// An error returned by an API route, as an HTTP status and a message.
+#[derive(Debug)]
+pub enum ApiError {
+    /// The requested type, entity or field doesn't exist.
+    NotFound(String),
+
+    /// The request is malformed, or can't be answered as asked.
+    BadRequest(String),
+
+    /// The request body is longer than the given number of bytes.
+    PayloadTooLarge(u64),
+
+    /// Something went wrong in the server; details are logged rather than returned.
+    Internal,
+}
+
+impl Error for ApiError {}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ApiError::NotFound(message) =>
+                write!(f, "{message}"),
+            ApiError::BadRequest(message) =>
+                write!(f, "{message}"),
+            ApiError::PayloadTooLarge(limit) =>
+                write!(f, "request body is larger than {limit} bytes"),
+            ApiError::Internal =>
+                write!(f, "internal server error"),
+        }
+    }
+}
+
+impl From<QueryError> for ApiError {
+    fn from(err: QueryError) -> ApiError {
+        ApiError::BadRequest(err.to_string())
+    }
+}
@@ -0,0 +1,412 @@
+use std::cmp::Ordering;
+
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::cache::TypeGroup;
+use crate::entity::{Entity, FieldData, FieldType, parse_date, parse_datetime};
+use crate::error::QueryError;
+use crate::schema::EntityDeclaration;
+
+/// Type of the `id` pseudo-field every entity can be filtered by.
+static ID_TYPE: FieldType = FieldType::Str;
+
+/// A filter on the entities of a query, as given in the query's JSON.
+///
+/// Keys are field names, or paths such as `seo.title` into objects, mapped to
+/// either a value the field must equal or an object of operators, e.g.
+/// `{ "rating": { "gte": 3 } }`. The `and`, `or` and `not` keys combine filters.
+#[derive(Debug, Default, Deserialize)]
+#[serde(transparent)]
+pub struct Filter(Map<String, Value>);
+
+#[derive(Debug)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Lte,
+    Gt,
+    Gte,
+    In,
+    Contains,
+    StartsWith,
+    Exists,
+}
+
+#[derive(Debug)]
+pub enum Operand {
+    Value(FieldData),
+    Values(Vec<FieldData>),
+    Flag(bool),
+}
+
+/// A [Filter] checked against the schema of the type it applies to.
+#[derive(Debug)]
+pub enum Condition<'q> {
+    And(Vec<Condition<'q>>),
+    Or(Vec<Condition<'q>>),
+    Not(Box<Condition<'q>>),
+
+    Field {
+        path: Vec<&'q str>,
+        op: Op,
+        operand: Operand,
+    },
+}
+
+impl Filter {
+    /// Checks the filter against the declaration of `group`, converting its
+    /// operands to the types of the fields they're compared with.
+    pub fn compile<'q>(&'q self, group: &TypeGroup) -> Result<Condition<'q>, QueryError> {
+        compile_map(&self.0, &group.declaration)
+    }
+}
+
+fn compile_map<'q>(
+    map: &'q Map<String, Value>,
+    decl: &EntityDeclaration
+) -> Result<Condition<'q>, QueryError> {
+    map.iter()
+        .map(|(key, value)| compile_entry(key, value, decl))
+        .collect::<Result<_, _>>()
+        .map(Condition::And)
+}
+
+fn compile_filter<'q>(
+    value: &'q Value,
+    decl: &EntityDeclaration
+) -> Result<Condition<'q>, QueryError> {
+    match value {
+        Value::Object(map) => compile_map(map, decl),
+
+        _ => Err(QueryError::InvalidFilter(format!("expected a filter, found {value}")))
+    }
+}
+
+fn compile_entry<'q>(
+    key: &'q str,
+    value: &'q Value,
+    decl: &EntityDeclaration
+) -> Result<Condition<'q>, QueryError> {
+    let compile_all = |filters: &'q Vec<Value>| filters.iter()
+        .map(|filter| compile_filter(filter, decl))
+        .collect::<Result<Vec<_>, _>>();
+
+    match (key, value) {
+        ("and", Value::Array(filters)) => compile_all(filters).map(Condition::And),
+        ("or", Value::Array(filters)) => compile_all(filters).map(Condition::Or),
+        ("not", filter) => Ok(Condition::Not(Box::new(compile_filter(filter, decl)?))),
+
+        ("and", _) | ("or", _) =>
+            Err(QueryError::InvalidFilter(format!(r#""{key}" expects a list of filters"#))),
+
+        _ => compile_field(key, value, decl)
+    }
+}
+
+fn compile_field<'q>(
+    key: &'q str,
+    value: &'q Value,
+    decl: &EntityDeclaration
+) -> Result<Condition<'q>, QueryError> {
+    let path: Vec<&str> = key.split('.').collect();
+    let ty = field_type(&path, decl)
+        .ok_or_else(|| QueryError::UnknownField(key.to_owned()))?;
+
+    // Anything other than an object of operators is shorthand for equality
+    let ops = match value {
+        Value::Object(ops) => ops.iter()
+            .map(|(op, operand)| compile_op(key, ty, op, operand))
+            .collect::<Result<Vec<_>, _>>()?,
+
+        _ => vec![compile_op(key, ty, "eq", value)?]
+    };
+
+    Ok(Condition::And(ops.into_iter()
+        .map(|(op, operand)| Condition::Field {
+            path: path.clone(),
+            op,
+            operand,
+        })
+        .collect()))
+}
+
+/// Type of the field at `path`, descending into objects.
+fn field_type<'d>(path: &[&str], decl: &'d EntityDeclaration) -> Option<&'d FieldType> {
+    match path {
+        ["id"] => Some(&ID_TYPE),
+        _ => decl.get_field(path).map(|field| &field.ty)
+    }
+}
+
+fn compile_op(key: &str, ty: &FieldType, op: &str, operand: &Value) -> Result<(Op, Operand), QueryError> {
+    let item_ty = ty.item_type();
+
+    let comparable = !matches!(item_ty, FieldType::Bin | FieldType::Object);
+    let orderable = matches!(item_ty, FieldType::Num | FieldType::Date | FieldType::DateTime);
+    let textual = matches!(item_ty, FieldType::Str | FieldType::Enum | FieldType::Ref(_));
+    let list = matches!(ty, FieldType::List(_));
+
+    let value = || operand_value(key, item_ty, operand).map(Operand::Value);
+
+    // Backlinks are computed from other entities rather than stored in fields
+    if let FieldType::Backlink = ty {
+        return Err(QueryError::InvalidFilter(format!(r#"backlink field "{key}" can't be filtered"#)));
+    }
+
+    match op {
+        "exists" => match operand {
+            Value::Bool(exists) => Ok((Op::Exists, Operand::Flag(*exists))),
+
+            _ => Err(QueryError::InvalidFilter(format!(r#""exists" on "{key}" expects true or false"#)))
+        },
+
+        "eq" if comparable => Ok((Op::Eq, value()?)),
+        "ne" if comparable => Ok((Op::Ne, value()?)),
+        "lt" if orderable => Ok((Op::Lt, value()?)),
+        "lte" if orderable => Ok((Op::Lte, value()?)),
+        "gt" if orderable => Ok((Op::Gt, value()?)),
+        "gte" if orderable => Ok((Op::Gte, value()?)),
+        "contains" if textual || (comparable && list) => Ok((Op::Contains, value()?)),
+        "starts_with" if textual => Ok((Op::StartsWith, value()?)),
+
+        "in" if comparable => match operand {
+            Value::Array(values) => values.iter()
+                .map(|value| operand_value(key, item_ty, value))
+                .collect::<Result<_, _>>()
+                .map(|values| (Op::In, Operand::Values(values))),
+
+            _ => Err(QueryError::InvalidFilter(format!(r#""in" on "{key}" expects a list of values"#)))
+        },
+
+        "eq" | "ne" | "lt" | "lte" | "gt" | "gte" | "in" | "contains" | "starts_with" =>
+            Err(QueryError::InvalidFilter(format!(r#""{op}" can't be applied to "{key}" of type "{ty}""#))),
+
+        _ => Err(QueryError::InvalidFilter(format!(r#"unknown filter operator "{op}" on "{key}""#)))
+    }
+}
+
+/// Converts a JSON operand to the type of the field it's compared with.
+/// Strings are parsed as the field's type, e.g. `"2020-01-01"` for dates.
+fn operand_value(key: &str, ty: &FieldType, value: &Value) -> Result<FieldData, QueryError> {
+    let data = match (ty, value) {
+        (FieldType::Str, Value::String(s)) |
+        (FieldType::Enum, Value::String(s)) |
+        (FieldType::Ref(_), Value::String(s)) => Some(FieldData::Str(s.clone())),
+
+        (FieldType::Num, Value::Number(n)) => n.as_f64().map(FieldData::Num),
+        (FieldType::Num, Value::String(s)) => s.parse().ok().map(FieldData::Num),
+        (FieldType::Bool, Value::Bool(b)) => Some(FieldData::Bool(*b)),
+        (FieldType::Bool, Value::String(s)) => s.parse().ok().map(FieldData::Bool),
+        (FieldType::Date, Value::String(s)) => parse_date(s).map(FieldData::Date),
+        (FieldType::DateTime, Value::String(s)) => parse_datetime(s).map(FieldData::DateTime),
+
+        _ => None
+    };
+
+    data.ok_or_else(|| QueryError::InvalidFilter(
+        format!(r#"{value} can't be compared with "{key}" of type "{ty}""#)
+    ))
+}
+
+impl<'q> Condition<'q> {
+    pub fn matches(&self, id: &str, entity: &Entity) -> bool {
+        match self {
+            Condition::And(conditions) => conditions.iter().all(|c| c.matches(id, entity)),
+            Condition::Or(conditions) => conditions.iter().any(|c| c.matches(id, entity)),
+            Condition::Not(condition) => !condition.matches(id, entity),
+
+            Condition::Field { path, op, operand } => {
+                let id_data;
+
+                let data = match path.as_slice() {
+                    ["id"] => {
+                        id_data = FieldData::Str(id.to_owned());
+                        Some(&id_data)
+                    },
+
+                    _ => entity.get_path(path)
+                };
+
+                matches_op(data, op, operand)
+            }
+        }
+    }
+
+    /// Field values every matching entity must equal, which can be resolved
+    /// through an index instead of checking every entity.
+    pub fn equalities(&self) -> Vec<(&'q str, &FieldData)> {
+        match self {
+            Condition::And(conditions) => conditions.iter()
+                .flat_map(|c| c.equalities())
+                .collect(),
+
+            Condition::Field { path, op: Op::Eq, operand: Operand::Value(value) } if path.len() == 1 =>
+                vec![(path[0], value)],
+
+            _ => vec![]
+        }
+    }
+}
+
+fn matches_op(data: Option<&FieldData>, op: &Op, operand: &Operand) -> bool {
+    let data = match (data, op, operand) {
+        (data, Op::Exists, Operand::Flag(exists)) => return data.is_some() == *exists,
+        (None, Op::Ne, _) => return true,
+        (None, _, _) => return false,
+        (Some(data), _, _) => data
+    };
+
+    // Lists match if any of their items do, or if none do for "ne"
+    match (data, op) {
+        (FieldData::List(items), Op::Ne) =>
+            !items.iter().any(|item| matches_item(item, &Op::Eq, operand)),
+        (FieldData::List(items), Op::Contains) =>
+            items.iter().any(|item| matches_item(item, &Op::Eq, operand)),
+        (FieldData::List(items), _) =>
+            items.iter().any(|item| matches_item(item, op, operand)),
+
+        _ => matches_item(data, op, operand)
+    }
+}
+
+fn matches_item(data: &FieldData, op: &Op, operand: &Operand) -> bool {
+    use Ordering::*;
+
+    match (op, operand, data) {
+        (Op::Eq, Operand::Value(value), _) => data.compare(value) == Some(Equal),
+        (Op::Ne, Operand::Value(value), _) => data.compare(value) != Some(Equal),
+        (Op::Lt, Operand::Value(value), _) => data.compare(value) == Some(Less),
+        (Op::Lte, Operand::Value(value), _) => matches!(data.compare(value), Some(Less) | Some(Equal)),
+        (Op::Gt, Operand::Value(value), _) => data.compare(value) == Some(Greater),
+        (Op::Gte, Operand::Value(value), _) => matches!(data.compare(value), Some(Greater) | Some(Equal)),
+
+        (Op::In, Operand::Values(values), _) =>
+            values.iter().any(|value| data.compare(value) == Some(Equal)),
+
+        (Op::Contains, Operand::Value(FieldData::Str(s)), FieldData::Str(d)) => d.contains(s.as_str()),
+        (Op::StartsWith, Operand::Value(FieldData::Str(s)), FieldData::Str(d)) => d.starts_with(s.as_str()),
+
+        _ => false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::cache::Cache;
+
+    fn cache() -> Cache {
+        let mut cache = Cache::new();
+
+        cache.add_type("Post", EntityDeclaration::from_str(r#"
+            [fields]
+            title = "str"
+            rating = "num"
+            published = "date"
+            tags = "[str]"
+            seo = { type = "object", fields = { rank = "num" } }
+        "#).unwrap());
+
+        let entities = [
+            ("a", r#"title = "Rust on embedded"
+                rating = 3
+                published = "2020-05-01"
+                tags = ["rust", "embedded"]
+                seo = { rank = 1 }"#),
+            ("b", r#"title = "A CMS in Go"
+                rating = 5
+                tags = ["go"]"#),
+            ("c", r#"title = "Untitled""#),
+        ];
+
+        for (id, ent) in entities.iter() {
+            cache.add_entity("Post", id, Entity::from_str(ent).unwrap());
+        }
+
+        cache
+    }
+
+    /// IDs of the entities matching `filter`, in order.
+    fn matching(filter: Value) -> Result<Vec<String>, QueryError> {
+        let cache = cache();
+        let group = cache.get_group("Post").unwrap();
+
+        let filter: Filter = serde_json::from_value(filter).unwrap();
+        let condition = filter.compile(group)?;
+
+        let mut ids: Vec<String> = group.entities.iter()
+            .filter(|(id, entity)| condition.matches(id, entity))
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        ids.sort();
+        Ok(ids)
+    }
+
+    #[test]
+    fn equality() {
+        assert_eq!(matching(json!({ "title": "Untitled" })).unwrap(), ["c"]);
+        assert_eq!(matching(json!({ "rating": { "eq": 5 } })).unwrap(), ["b"]);
+        assert_eq!(matching(json!({ "id": "a" })).unwrap(), ["a"]);
+        assert_eq!(matching(json!({ "seo.rank": 1 })).unwrap(), ["a"]);
+    }
+
+    #[test]
+    fn comparisons() {
+        assert_eq!(matching(json!({ "rating": { "lt": 5 } })).unwrap(), ["a"]);
+        assert_eq!(matching(json!({ "rating": { "lte": 5 } })).unwrap(), ["a", "b"]);
+        assert_eq!(matching(json!({ "rating": { "gt": 3, "lte": 5 } })).unwrap(), ["b"]);
+        assert_eq!(matching(json!({ "rating": { "gte": "3" } })).unwrap(), ["a", "b"]);
+        assert_eq!(matching(json!({ "published": { "gte": "2020-01-01" } })).unwrap(), ["a"]);
+    }
+
+    #[test]
+    fn text_and_sets() {
+        assert_eq!(matching(json!({ "title": { "contains": "CMS" } })).unwrap(), ["b"]);
+        assert_eq!(matching(json!({ "title": { "starts_with": "Rust" } })).unwrap(), ["a"]);
+        assert_eq!(matching(json!({ "rating": { "in": [1, 5] } })).unwrap(), ["b"]);
+    }
+
+    #[test]
+    fn lists() {
+        assert_eq!(matching(json!({ "tags": "go" })).unwrap(), ["b"]);
+        assert_eq!(matching(json!({ "tags": { "contains": "rust" } })).unwrap(), ["a"]);
+        assert_eq!(matching(json!({ "tags": { "in": ["go", "rust"] } })).unwrap(), ["a", "b"]);
+
+        // Lists are only unequal to values none of their items equal
+        assert_eq!(matching(json!({ "tags": { "ne": "rust" } })).unwrap(), ["b", "c"]);
+    }
+
+    #[test]
+    fn missing_fields() {
+        // Only "ne" and "exists": false match entities lacking the field
+        assert_eq!(matching(json!({ "rating": { "ne": 3 } })).unwrap(), ["b", "c"]);
+        assert_eq!(matching(json!({ "rating": { "lt": 100 } })).unwrap(), ["a", "b"]);
+        assert_eq!(matching(json!({ "rating": { "exists": false } })).unwrap(), ["c"]);
+        assert_eq!(matching(json!({ "seo.rank": { "exists": true } })).unwrap(), ["a"]);
+    }
+
+    #[test]
+    fn combinators() {
+        assert_eq!(matching(json!({ "or": [{ "rating": 3 }, { "title": "Untitled" }] })).unwrap(), ["a", "c"]);
+        assert_eq!(matching(json!({ "and": [{ "rating": { "gte": 3 } }, { "tags": "go" }] })).unwrap(), ["b"]);
+        assert_eq!(matching(json!({ "not": { "rating": 3 } })).unwrap(), ["b", "c"]);
+    }
+
+    #[test]
+    fn invalid_filters() {
+        assert!(matches!(matching(json!({ "author": "x" })), Err(QueryError::UnknownField(_))));
+        assert!(matches!(matching(json!({ "title": { "like": "x" } })), Err(QueryError::InvalidFilter(_))));
+        assert!(matches!(matching(json!({ "title": { "lt": "x" } })), Err(QueryError::InvalidFilter(_))));
+        assert!(matches!(matching(json!({ "rating": "many" })), Err(QueryError::InvalidFilter(_))));
+        assert!(matches!(matching(json!({ "rating": { "in": 3 } })), Err(QueryError::InvalidFilter(_))));
+        assert!(matches!(matching(json!({ "or": { "rating": 3 } })), Err(QueryError::InvalidFilter(_))));
+        assert!(matches!(matching(json!({ "seo": { "exists": "yes" } })), Err(QueryError::InvalidFilter(_))));
+    }
+}
@@ -0,0 +1,679 @@
+use std::collections::{HashMap, HashSet};
+
+use graphql_parser::query as ast;
+use serde_json::{Map, Value};
+
+use crate::asset;
+use crate::cache::Cache;
+use crate::entity::{Entity, FieldData, FieldType};
+use crate::query::{Page, QueryEntity};
+use crate::schema::FieldDeclaration;
+
+use super::schema::{
+    DirectiveDef, FieldDef, InputValueDef, Schema, TypeDef, TypeKind, TypeRef,
+    QUERY_TYPE, entity_field, list_field, page_type
+};
+use super::ResponseError;
+
+type Document<'d> = ast::Document<'d, &'d str>;
+type Selection<'d> = ast::Selection<'d, &'d str>;
+type Directive<'d> = ast::Directive<'d, &'d str>;
+type AstValue<'d> = ast::Value<'d, &'d str>;
+type Fragments<'d> = HashMap<&'d str, &'d ast::FragmentDefinition<'d, &'d str>>;
+
+/// Maximum number of levels of fields selected by a query, which bounds the
+/// expansion of references. Introspection queries select about a dozen.
+const MAX_DEPTH: usize = 16;
+
+/// Something fields can be selected on.
+enum Node<'a> {
+    Query,
+
+    Entity {
+        ty: &'a str,
+        id: &'a str,
+        entity: &'a Entity,
+    },
+
+    /// Value of an object field, named after the field as in the schema.
+    Object {
+        name: String,
+        decls: &'a HashMap<String, FieldDeclaration>,
+        fields: &'a HashMap<String, FieldData>,
+    },
+
+    Page {
+        ty: &'a str,
+        page: Page<'a>,
+    },
+
+    // Introspection
+    Schema,
+    Type(&'a TypeDef),
+    Wrapper(&'a TypeRef),
+    Field(&'a FieldDef),
+    InputValue(&'a InputValueDef),
+    EnumValue(&'a str),
+    Directive(&'a DirectiveDef),
+}
+
+impl<'a> Node<'a> {
+    fn type_name(&self) -> String {
+        match self {
+            Node::Query => QUERY_TYPE.to_owned(),
+            Node::Entity { ty, .. } => ty.to_string(),
+            Node::Object { name, .. } => name.clone(),
+            Node::Page { ty, .. } => page_type(ty),
+            Node::Schema => "__Schema".to_owned(),
+            Node::Type(_) | Node::Wrapper(_) => "__Type".to_owned(),
+            Node::Field(_) => "__Field".to_owned(),
+            Node::InputValue(_) => "__InputValue".to_owned(),
+            Node::EnumValue(_) => "__EnumValue".to_owned(),
+            Node::Directive(_) => "__Directive".to_owned()
+        }
+    }
+}
+
+/// A resolved field, before its own fields are selected.
+enum Resolved<'a> {
+    Value(Value),
+    Node(Node<'a>),
+    List(Vec<Resolved<'a>>),
+}
+
+impl<'a> From<Option<&str>> for Resolved<'a> {
+    fn from(s: Option<&str>) -> Resolved<'a> {
+        Resolved::Value(s.map_or(Value::Null, |s| Value::String(s.to_owned())))
+    }
+}
+
+pub struct Executor<'a, 'd> {
+    cache: &'a Cache,
+    schema: &'a Schema,
+    fragments: Fragments<'d>,
+    variables: Map<String, Value>,
+    pub errors: Vec<ResponseError>,
+}
+
+impl<'a, 'd> Executor<'a, 'd> {
+    pub fn new(cache: &'a Cache, document: &'d Document<'d>) -> Self {
+        let fragments = document.definitions.iter()
+            .filter_map(|definition| match definition {
+                ast::Definition::Fragment(fragment) => Some((fragment.name, fragment)),
+                _ => None
+            })
+            .collect();
+
+        Executor {
+            cache,
+            schema: cache.graphql_schema(),
+            fragments,
+            variables: Map::new(),
+            errors: vec![],
+        }
+    }
+
+    /// Executes the operation named `operation_name`, or the only one in the
+    /// document if not given.
+    pub fn execute(
+        &mut self,
+        document: &'d Document<'d>,
+        operation_name: Option<&str>,
+        variables: &Map<String, Value>
+    ) -> Result<Value, String> {
+        let operations: Vec<_> = document.definitions.iter()
+            .filter_map(|definition| match definition {
+                ast::Definition::Operation(operation) => Some(operation),
+                _ => None
+            })
+            .collect();
+
+        let operation = match operation_name {
+            Some(name) => operations.into_iter()
+                .find(|operation| operation_name_of(operation) == Some(name))
+                .ok_or_else(|| format!(r#"unknown operation "{name}""#))?,
+
+            None if operations.len() == 1 => operations[0],
+            None => return Err("an operation name is required when there's more than one operation".to_owned())
+        };
+
+        let (definitions, selection_set) = match operation {
+            ast::OperationDefinition::SelectionSet(selection_set) => (&[][..], selection_set),
+            ast::OperationDefinition::Query(query) => (&query.variable_definitions[..], &query.selection_set),
+
+            _ => return Err("only queries are supported".to_owned())
+        };
+
+        // Fragments must not form cycles, even those the operation doesn't use
+        let mut depths = HashMap::new();
+
+        for name in self.fragments.keys() {
+            fragment_depth(&self.fragments, name, &mut depths, &mut vec![])?;
+        }
+
+        if selection_depth(&self.fragments, &selection_set.items, &mut depths, &mut vec![])? > MAX_DEPTH {
+            return Err(format!("queries can't select fields more than {MAX_DEPTH} levels deep"));
+        }
+
+        for definition in definitions {
+            let value = match (variables.get(definition.name), &definition.default_value) {
+                (Some(value), _) => value.clone(),
+                (None, Some(default)) => self.to_json(default),
+                (None, None) if matches!(definition.var_type, ast::Type::NonNullType(_)) =>
+                    return Err(format!(r#"variable "${}" is required"#, definition.name)),
+                (None, None) => Value::Null
+            };
+
+            self.variables.insert(definition.name.to_owned(), value);
+        }
+
+        let items: Vec<&Selection> = selection_set.items.iter().collect();
+
+        Ok(self.select(&Node::Query, &items, &mut vec![]))
+    }
+
+    /// Selects fields on `node`, merging fields with the same response key.
+    fn select(&mut self, node: &Node<'a>, items: &[&'d Selection<'d>], path: &mut Vec<Value>) -> Value {
+        let mut grouped: Vec<(&'d str, Vec<&'d ast::Field<'d, &'d str>>)> = vec![];
+
+        self.collect_fields(&node.type_name(), items, &mut grouped, &mut HashSet::new());
+
+        let mut result = Map::new();
+
+        for (key, fields) in grouped {
+            let field = fields[0];
+            let children: Vec<&Selection> = fields.iter()
+                .flat_map(|field| field.selection_set.items.iter())
+                .collect();
+
+            path.push(Value::String(key.to_owned()));
+
+            let args = field.arguments.iter()
+                .map(|(name, value)| (name.to_string(), self.to_json(value)))
+                .collect();
+
+            let value = match self.resolve(node, field.name, &args) {
+                Ok(resolved) => self.complete(resolved, &children, path),
+
+                Err(message) => {
+                    self.error(message, path);
+                    Value::Null
+                }
+            };
+
+            path.pop();
+            result.insert(key.to_owned(), value);
+        }
+
+        Value::Object(result)
+    }
+
+    /// Fields selected on an object of type `ty` by `items`, following
+    /// fragments which apply to the type and dropping skipped fields.
+    fn collect_fields(
+        &mut self,
+        ty: &str,
+        items: &[&'d Selection<'d>],
+        grouped: &mut Vec<(&'d str, Vec<&'d ast::Field<'d, &'d str>>)>,
+        visited: &mut HashSet<&'d str>
+    ) {
+        for item in items {
+            match item {
+                ast::Selection::Field(field) if self.included(&field.directives) => {
+                    let key = field.alias.unwrap_or(field.name);
+
+                    match grouped.iter_mut().find(|(other, _)| *other == key) {
+                        Some((_, fields)) => fields.push(field),
+                        None => grouped.push((key, vec![field]))
+                    }
+                },
+
+                ast::Selection::FragmentSpread(spread) if self.included(&spread.directives) => {
+                    let name = spread.fragment_name;
+
+                    // Following each fragment once per selection also stops cycles
+                    if !visited.insert(name) {
+                        continue;
+                    }
+
+                    let fragment = match self.fragments.get(name) {
+                        Some(fragment) => *fragment,
+
+                        None => {
+                            self.error(format!(r#"unknown fragment "{name}""#), &[]);
+                            continue;
+                        }
+                    };
+
+                    let ast::TypeCondition::On(condition) = fragment.type_condition;
+
+                    if condition == ty {
+                        let items: Vec<&Selection> = fragment.selection_set.items.iter().collect();
+                        self.collect_fields(ty, &items, grouped, visited);
+                    }
+                },
+
+                ast::Selection::InlineFragment(fragment) if self.included(&fragment.directives) => {
+                    let applies = match &fragment.type_condition {
+                        Some(ast::TypeCondition::On(condition)) => *condition == ty,
+                        None => true
+                    };
+
+                    if applies {
+                        let items: Vec<&Selection> = fragment.selection_set.items.iter().collect();
+                        self.collect_fields(ty, &items, grouped, visited);
+                    }
+                },
+
+                _ => ()
+            }
+        }
+    }
+
+    /// Whether the `@skip` and `@include` directives keep a selection.
+    fn included(&self, directives: &[Directive<'d>]) -> bool {
+        directives.iter().all(|directive| {
+            let condition = directive.arguments.iter()
+                .find(|(name, _)| *name == "if")
+                .map(|(_, value)| self.to_json(value));
+
+            match (directive.name, condition) {
+                ("skip", Some(Value::Bool(skip))) => !skip,
+                ("include", Some(Value::Bool(include))) => include,
+
+                _ => true
+            }
+        })
+    }
+
+    fn complete(&mut self, resolved: Resolved<'a>, items: &[&'d Selection<'d>], path: &mut Vec<Value>) -> Value {
+        match resolved {
+            Resolved::Value(value) => value,
+
+            Resolved::Node(node) if items.is_empty() => {
+                self.error(format!(r#"a selection of fields is required on "{}""#, node.type_name()), path);
+                Value::Null
+            },
+
+            Resolved::Node(node) => self.select(&node, items, path),
+
+            Resolved::List(list) => Value::Array(list.into_iter()
+                .enumerate()
+                .map(|(i, resolved)| {
+                    path.push(i.into());
+                    let value = self.complete(resolved, items, path);
+                    path.pop();
+
+                    value
+                })
+                .collect())
+        }
+    }
+
+    fn resolve(&self, node: &Node<'a>, field: &str, args: &Map<String, Value>) -> Result<Resolved<'a>, String> {
+        if field == "__typename" {
+            return Ok(Resolved::Value(Value::String(node.type_name())));
+        }
+
+        let resolved = match node {
+            Node::Query => self.resolve_query(field, args)?,
+
+            Node::Entity { ty, id, entity } => match field {
+                "id" => Some(Some(*id).into()),
+
+                _ => self.cache.get_group(ty).and_then(|group| self.resolve_declared(
+                    ty, Some(id), &group.declaration.fields, &entity.fields, field
+                ))
+            },
+
+            Node::Object { name, decls, fields } => self.resolve_declared(name, None, decls, fields, field),
+
+            Node::Page { ty, page } => match field {
+                "total" => Some(Resolved::Value(page.total.into())),
+                "next" => Some(page.next.as_deref().into()),
+                "prev" => Some(page.prev.as_deref().into()),
+
+                "entities" => Some(Resolved::List(page.entities.iter()
+                    .map(|(id, entity)| Resolved::Node(Node::Entity { ty, id, entity }))
+                    .collect())),
+
+                _ => None
+            },
+
+            _ => self.resolve_introspection(node, field)
+        };
+
+        resolved.ok_or_else(|| format!(r#"no such field "{field}" on type "{}""#, node.type_name()))
+    }
+
+    fn resolve_query(&self, field: &str, args: &Map<String, Value>) -> Result<Option<Resolved<'a>>, String> {
+        match field {
+            "__schema" => return Ok(Some(Resolved::Node(Node::Schema))),
+
+            "__type" => {
+                let ty = args.get("name")
+                    .and_then(Value::as_str)
+                    .and_then(|name| self.schema.get_type(name));
+
+                return Ok(Some(ty.map_or(Resolved::Value(Value::Null), |ty| Resolved::Node(Node::Type(ty)))));
+            },
+
+            _ => ()
+        }
+
+        let schema = self.schema;
+
+        let root_field = schema.entity_types.iter()
+            .filter_map(|ty| Some((ty, self.cache.get_group(ty)?)))
+            .find_map(|(ty, group)| match field {
+                _ if field == entity_field(ty) => Some((ty, group, false)),
+                _ if field == list_field(ty) => Some((ty, group, true)),
+                _ => None
+            });
+
+        let (ty, group, list) = match root_field {
+            Some(root_field) => root_field,
+            None => return Ok(None)
+        };
+
+        if !list {
+            let id = args.get("id")
+                .and_then(Value::as_str)
+                .ok_or_else(|| r#""id" is required"#.to_owned())?;
+
+            return Ok(Some(match group.entities.get_key_value(id) {
+                Some((id, entity)) => Resolved::Node(Node::Entity { ty, id, entity }),
+                None => Resolved::Value(Value::Null)
+            }));
+        }
+
+        // Arguments are the same as the clauses of /query
+        let mut query: Map<String, Value> = args.iter()
+            .filter(|(_, value)| !value.is_null())
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+
+        query.insert("fields".to_owned(), Value::Array(vec![]));
+
+        let query: QueryEntity = serde_json::from_value(Value::Object(query))
+            .map_err(|e| e.to_string())?;
+
+        let entities = query.select(self.cache, ty).map_err(|e| e.to_string())?;
+        let page = query.page(&entities).map_err(|e| e.to_string())?;
+
+        Ok(Some(Resolved::Node(Node::Page { ty, page })))
+    }
+
+    /// Resolves a declared field of an entity, or of an object in one if `id`
+    /// is `None`.
+    fn resolve_declared(
+        &self,
+        parent: &str,
+        id: Option<&'a str>,
+        decls: &'a HashMap<String, FieldDeclaration>,
+        fields: &'a HashMap<String, FieldData>,
+        field: &str
+    ) -> Option<Resolved<'a>> {
+        // Fields left out of the schema can't be selected either
+        self.schema.get_type(parent)?.fields.iter().find(|def| def.name == field)?;
+
+        let (name, decl) = decls.get_key_value(field)?;
+
+        if let Some(backlink) = &decl.backlink {
+            let ids = id.map_or(&[][..], |id| self.cache.get_backlinks(backlink, id));
+
+            return Some(Resolved::List(ids.iter()
+                .map(|ref_id| self.resolve_ref(&backlink.ty, ref_id))
+                .collect()));
+        }
+
+        // Binary fields resolve to the URL serving their content
+        let url = id.map(|id| asset::field_url(parent, id, name));
+
+        Some(match fields.get(field) {
+            Some(data) => self.resolve_data(&format!("{parent}_{name}"), url, decl, &decl.ty, data),
+            None => Resolved::Value(Value::Null)
+        })
+    }
+
+    fn resolve_data(
+        &self,
+        object_name: &str,
+        url: Option<String>,
+        decl: &'a FieldDeclaration,
+        ty: &'a FieldType,
+        data: &'a FieldData
+    ) -> Resolved<'a> {
+        match (ty, data) {
+            (FieldType::Ref(ref_ty), FieldData::Str(ref_id)) => self.resolve_ref(ref_ty, ref_id),
+
+            (FieldType::List(item_ty), FieldData::List(items)) => Resolved::List(items.iter()
+                .enumerate()
+                .map(|(i, item)| {
+                    // Only items of top-level lists are served individually
+                    let url = match **item_ty {
+                        FieldType::List(_) => None,
+                        _ => url.as_ref().map(|url| format!("{url}/{i}"))
+                    };
+
+                    self.resolve_data(object_name, url, decl, item_ty, item)
+                })
+                .collect()),
+
+            (FieldType::Object, FieldData::Object(fields)) => Resolved::Node(Node::Object {
+                name: object_name.to_owned(),
+                decls: &decl.fields,
+                fields,
+            }),
+
+            (_, FieldData::Bin(_)) | (_, FieldData::File(_)) => url.as_deref().into(),
+
+            _ => Resolved::Value(serde_json::to_value(data).unwrap_or(Value::Null))
+        }
+    }
+
+    /// References to types left out of the schema are given as IDs.
+    fn resolve_ref(&self, ty: &'a str, id: &'a str) -> Resolved<'a> {
+        let entity = Some(ty)
+            .filter(|ty| self.schema.is_entity_type(ty))
+            .and_then(|ty| self.cache.get_group(ty))
+            .map(|group| group.entities.get_key_value(id));
+
+        match entity {
+            Some(Some((id, entity))) => Resolved::Node(Node::Entity { ty, id, entity }),
+            Some(None) => Resolved::Value(Value::Null),
+            None => Some(id).into()
+        }
+    }
+
+    fn resolve_introspection(&self, node: &Node<'a>, field: &str) -> Option<Resolved<'a>> {
+        let null = || Resolved::Value(Value::Null);
+        let list = |nodes: Vec<Node<'a>>| Resolved::List(nodes.into_iter().map(Resolved::Node).collect());
+        let inputs = |args: &'a [InputValueDef]| list(args.iter().map(Node::InputValue).collect());
+
+        let resolved = match (node, field) {
+            (Node::Schema, "types") => list(self.schema.types.values().map(Node::Type).collect()),
+            (Node::Schema, "queryType") => self.schema.get_type(QUERY_TYPE).map_or_else(null, |ty| Resolved::Node(Node::Type(ty))),
+            (Node::Schema, "mutationType") | (Node::Schema, "subscriptionType") => null(),
+            (Node::Schema, "directives") => list(self.schema.directives.iter().map(Node::Directive).collect()),
+            (Node::Schema, "description") => null(),
+
+            (Node::Type(ty), "kind") => Some(ty.kind.name()).into(),
+            (Node::Type(ty), "name") => Some(ty.name.as_str()).into(),
+            (Node::Type(ty), "description") => ty.description.as_deref().into(),
+
+            (Node::Type(ty), "fields") => match ty.kind {
+                TypeKind::Object => list(ty.fields.iter().map(Node::Field).collect()),
+                _ => null()
+            },
+
+            (Node::Type(ty), "interfaces") => match ty.kind {
+                TypeKind::Object => Resolved::List(vec![]),
+                _ => null()
+            },
+
+            (Node::Type(ty), "enumValues") => match ty.kind {
+                TypeKind::Enum => list(ty.enum_values.iter().map(|v| Node::EnumValue(v)).collect()),
+                _ => null()
+            },
+
+            (Node::Type(ty), "inputFields") => match ty.kind {
+                TypeKind::InputObject => inputs(&ty.input_fields),
+                _ => null()
+            },
+
+            (Node::Type(_), "possibleTypes") | (Node::Type(_), "ofType") |
+            (Node::Type(_), "specifiedByURL") | (Node::Type(_), "specifiedByUrl") => null(),
+
+            (Node::Wrapper(ty), "kind") => match ty {
+                TypeRef::List(_) => Some("LIST").into(),
+                _ => Some("NON_NULL").into()
+            },
+
+            (Node::Wrapper(ty), "ofType") => match ty {
+                TypeRef::List(inner) | TypeRef::NonNull(inner) => self.type_ref(inner),
+                TypeRef::Named(_) => null()
+            },
+
+            (Node::Wrapper(_), "name") | (Node::Wrapper(_), "description") |
+            (Node::Wrapper(_), "fields") | (Node::Wrapper(_), "interfaces") |
+            (Node::Wrapper(_), "possibleTypes") | (Node::Wrapper(_), "enumValues") |
+            (Node::Wrapper(_), "inputFields") | (Node::Wrapper(_), "specifiedByURL") |
+            (Node::Wrapper(_), "specifiedByUrl") => null(),
+
+            (Node::Field(f), "name") => Some(f.name.as_str()).into(),
+            (Node::Field(f), "description") => f.description.as_deref().into(),
+            (Node::Field(f), "args") => inputs(&f.args),
+            (Node::Field(f), "type") => self.type_ref(&f.ty),
+
+            (Node::InputValue(v), "name") => Some(v.name.as_str()).into(),
+            (Node::InputValue(v), "description") => v.description.as_deref().into(),
+            (Node::InputValue(v), "type") => self.type_ref(&v.ty),
+            (Node::InputValue(_), "defaultValue") => null(),
+
+            (Node::EnumValue(v), "name") => Some(*v).into(),
+            (Node::EnumValue(_), "description") => null(),
+
+            (Node::Directive(d), "name") => Some(d.name.as_str()).into(),
+            (Node::Directive(d), "description") => d.description.as_deref().into(),
+            (Node::Directive(d), "args") => inputs(&d.args),
+            (Node::Directive(_), "isRepeatable") => Resolved::Value(false.into()),
+
+            (Node::Directive(d), "locations") => Resolved::List(d.locations.iter()
+                .map(|location| Some(*location).into())
+                .collect()),
+
+            // Nothing is deprecated
+            (Node::Field(_), "isDeprecated") | (Node::InputValue(_), "isDeprecated") |
+            (Node::EnumValue(_), "isDeprecated") => Resolved::Value(false.into()),
+
+            (Node::Field(_), "deprecationReason") | (Node::InputValue(_), "deprecationReason") |
+            (Node::EnumValue(_), "deprecationReason") => null(),
+
+            _ => return None
+        };
+
+        Some(resolved)
+    }
+
+    fn type_ref(&self, ty: &'a TypeRef) -> Resolved<'a> {
+        match ty {
+            TypeRef::Named(name) => self.schema.get_type(name)
+                .map_or(Resolved::Value(Value::Null), |ty| Resolved::Node(Node::Type(ty))),
+
+            _ => Resolved::Node(Node::Wrapper(ty))
+        }
+    }
+
+    /// Converts an argument to JSON, substituting variables.
+    fn to_json(&self, value: &AstValue<'d>) -> Value {
+        match value {
+            ast::Value::Variable(name) => self.variables.get(*name).cloned().unwrap_or(Value::Null),
+            ast::Value::Int(n) => n.as_i64().map_or(Value::Null, Value::from),
+            ast::Value::Float(f) => serde_json::Number::from_f64(*f).map_or(Value::Null, Value::Number),
+            ast::Value::String(s) => Value::String(s.clone()),
+            ast::Value::Boolean(b) => Value::Bool(*b),
+            ast::Value::Null => Value::Null,
+            ast::Value::Enum(e) => Value::String(e.to_string()),
+            ast::Value::List(items) => Value::Array(items.iter().map(|item| self.to_json(item)).collect()),
+
+            ast::Value::Object(fields) => Value::Object(fields.iter()
+                .map(|(name, value)| (name.to_string(), self.to_json(value)))
+                .collect())
+        }
+    }
+
+    fn error(&mut self, message: String, path: &[Value]) {
+        self.errors.push(ResponseError {
+            message,
+            path: path.to_vec(),
+        });
+    }
+}
+
+/// Levels of fields selected by `items`, following fragments. `depths` holds
+/// the depths of fragments already measured, and `spreading` the fragments
+/// being measured, so fragments which spread themselves are rejected.
+fn selection_depth<'d>(
+    fragments: &Fragments<'d>,
+    items: &'d [Selection<'d>],
+    depths: &mut HashMap<&'d str, usize>,
+    spreading: &mut Vec<&'d str>
+) -> Result<usize, String> {
+    let mut depth = 0;
+
+    for item in items {
+        let item_depth = match item {
+            ast::Selection::Field(field) =>
+                1 + selection_depth(fragments, &field.selection_set.items, depths, spreading)?,
+
+            ast::Selection::FragmentSpread(spread) =>
+                fragment_depth(fragments, spread.fragment_name, depths, spreading)?,
+
+            ast::Selection::InlineFragment(fragment) =>
+                selection_depth(fragments, &fragment.selection_set.items, depths, spreading)?
+        };
+
+        depth = depth.max(item_depth);
+    }
+
+    Ok(depth)
+}
+
+fn fragment_depth<'d>(
+    fragments: &Fragments<'d>,
+    name: &'d str,
+    depths: &mut HashMap<&'d str, usize>,
+    spreading: &mut Vec<&'d str>
+) -> Result<usize, String> {
+    if let Some(depth) = depths.get(name) {
+        return Ok(*depth);
+    }
+
+    if spreading.contains(&name) {
+        return Err(format!(r#"fragment "{name}" spreads itself"#));
+    }
+
+    // Unknown fragments are reported where they're spread
+    let fragment = match fragments.get(name) {
+        Some(fragment) => *fragment,
+        None => return Ok(0)
+    };
+
+    spreading.push(name);
+    let depth = selection_depth(fragments, &fragment.selection_set.items, depths, spreading)?;
+    spreading.pop();
+
+    depths.insert(name, depth);
+
+    Ok(depth)
+}
+
+fn operation_name_of<'d>(operation: &ast::OperationDefinition<'d, &'d str>) -> Option<&'d str> {
+    match operation {
+        ast::OperationDefinition::SelectionSet(_) => None,
+        ast::OperationDefinition::Query(query) => query.name,
+        ast::OperationDefinition::Mutation(mutation) => mutation.name,
+        ast::OperationDefinition::Subscription(subscription) => subscription.name
+    }
+}
@@ -0,0 +1,72 @@
+mod execute;
+mod schema;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::cache::Cache;
+
+use execute::Executor;
+
+pub use schema::Schema;
+
+/// A GraphQL request, as sent by GraphiQL and most other clients.
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    pub query: String,
+
+    #[serde(default)]
+    pub variables: Option<Map<String, Value>>,
+
+    #[serde(default, rename = "operationName")]
+    pub operation_name: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Response {
+    /// Result of the operation, or `None` if it couldn't be executed at all.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<ResponseError>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ResponseError {
+    pub message: String,
+
+    /// Response keys and list indices leading to the field which failed.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub path: Vec<Value>,
+}
+
+impl Response {
+    fn error(message: String) -> Response {
+        Response {
+            data: None,
+            errors: vec![ResponseError { message, path: vec![] }],
+        }
+    }
+}
+
+/// Executes a request against the schema generated for `cache`. Fields which
+/// fail are returned as `null` alongside an error.
+pub fn execute(cache: &Cache, request: &Request) -> Response {
+    let document = match graphql_parser::query::parse_query::<&str>(&request.query) {
+        Ok(document) => document,
+        Err(e) => return Response::error(e.to_string())
+    };
+
+    let mut executor = Executor::new(cache, &document);
+    let variables = request.variables.clone().unwrap_or_default();
+
+    match executor.execute(&document, request.operation_name.as_deref(), &variables) {
+        Ok(data) => Response {
+            data: Some(data),
+            errors: executor.errors,
+        },
+
+        Err(message) => Response::error(message)
+    }
+}
@@ -0,0 +1,399 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::cache::Cache;
+use crate::entity::FieldType;
+use crate::schema::FieldDeclaration;
+
+/// Name of the root type queries are executed against.
+pub const QUERY_TYPE: &str = "Query";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TypeKind {
+    Scalar,
+    Object,
+    InputObject,
+    Enum,
+}
+
+impl TypeKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TypeKind::Scalar => "SCALAR",
+            TypeKind::Object => "OBJECT",
+            TypeKind::InputObject => "INPUT_OBJECT",
+            TypeKind::Enum => "ENUM"
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeRef {
+    Named(String),
+    List(Box<TypeRef>),
+    NonNull(Box<TypeRef>),
+}
+
+impl TypeRef {
+    fn named(name: &str) -> TypeRef {
+        TypeRef::Named(name.to_owned())
+    }
+
+    fn non_null(self) -> TypeRef {
+        TypeRef::NonNull(Box::new(self))
+    }
+
+    fn list(self) -> TypeRef {
+        TypeRef::List(Box::new(self))
+    }
+}
+
+#[derive(Debug)]
+pub struct TypeDef {
+    pub name: String,
+    pub kind: TypeKind,
+    pub description: Option<String>,
+
+    /// Fields of an object.
+    pub fields: Vec<FieldDef>,
+
+    /// Fields of an input object.
+    pub input_fields: Vec<InputValueDef>,
+
+    /// Values of an enum.
+    pub enum_values: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct FieldDef {
+    pub name: String,
+    pub description: Option<String>,
+    pub args: Vec<InputValueDef>,
+    pub ty: TypeRef,
+}
+
+#[derive(Debug)]
+pub struct InputValueDef {
+    pub name: String,
+    pub description: Option<String>,
+    pub ty: TypeRef,
+}
+
+#[derive(Debug)]
+pub struct DirectiveDef {
+    pub name: String,
+    pub description: Option<String>,
+    pub locations: Vec<&'static str>,
+    pub args: Vec<InputValueDef>,
+}
+
+/// GraphQL schema generated from the entity declarations of a [Cache], used
+/// to answer introspection queries.
+///
+/// Every entity type becomes an object type of the same name, with a root
+/// field to get one entity by ID (`post(id: ...)`) and one to list entities
+/// (`allPost(...)`). Types and fields whose names aren't valid GraphQL names
+/// are left out, as are types whose names clash with those of other types.
+#[derive(Debug, Default)]
+pub struct Schema {
+    pub types: BTreeMap<String, TypeDef>,
+    pub directives: Vec<DirectiveDef>,
+
+    /// Entity types in the schema, in order of their names.
+    pub entity_types: Vec<String>,
+
+    /// Types and fields left out of the schema as their names clash.
+    pub warnings: Vec<String>,
+}
+
+impl Schema {
+    pub fn generate(cache: &Cache) -> Schema {
+        let mut schema = Schema::default();
+
+        schema.add_builtins();
+
+        let mut names: Vec<&String> = cache.groups()
+            .map(|(name, _)| name)
+            .filter(|name| is_name(name))
+            .collect();
+
+        names.sort();
+
+        // Types whose names, or the names of their page types and root fields,
+        // are taken by a builtin or an earlier type are left out
+        let mut type_names: HashSet<String> = schema.types.keys().cloned().collect();
+        let mut root_field_names = HashSet::new();
+
+        type_names.insert(QUERY_TYPE.to_owned());
+
+        for name in names {
+            let types = [name.clone(), page_type(name)];
+            let root_fields = [entity_field(name), list_field(name)];
+
+            if types.iter().any(|ty| type_names.contains(ty)) ||
+                root_fields.iter().any(|field| root_field_names.contains(field))
+            {
+                schema.warnings.push(format!(
+                    r#"type "{name}" was left out of the GraphQL schema, as its names clash with another type"#
+                ));
+                continue;
+            }
+
+            type_names.extend(types.iter().cloned());
+            root_field_names.extend(root_fields.iter().cloned());
+
+            schema.entity_types.push(name.clone());
+        }
+
+        let mut root_fields = vec![];
+
+        for ty_name in schema.entity_types.clone() {
+            let ty_name = ty_name.as_str();
+            let group = match cache.get_group(ty_name) {
+                Some(group) => group,
+                None => continue
+            };
+
+            let mut fields = vec![FieldDef {
+                name: "id".to_owned(),
+                description: None,
+                args: vec![],
+                ty: TypeRef::named("ID").non_null(),
+            }];
+
+            fields.extend(schema.field_defs(ty_name, &group.declaration.fields));
+
+            schema.add_type(ty_name, TypeKind::Object, fields);
+
+            schema.add_type(&page_type(ty_name), TypeKind::Object, vec![
+                field("total", TypeRef::named("Int").non_null(), "Number of matching entities across all pages."),
+                field("next", TypeRef::named("String"), "Cursor of the following page."),
+                field("prev", TypeRef::named("String"), "Cursor of the preceding page."),
+                field("entities", TypeRef::named(ty_name).non_null().list().non_null(), "Entities on this page."),
+            ]);
+
+            root_fields.push(FieldDef {
+                name: entity_field(ty_name),
+                description: Some(format!("Gets a single {ty_name} by ID.")),
+                args: vec![input("id", TypeRef::named("ID").non_null(), None)],
+                ty: TypeRef::named(ty_name),
+            });
+
+            root_fields.push(FieldDef {
+                name: list_field(ty_name),
+                description: Some(format!("Lists the {ty_name} entities matching a filter and search.")),
+                args: list_args(),
+                ty: TypeRef::named(&page_type(ty_name)).non_null(),
+            });
+        }
+
+        schema.add_type(QUERY_TYPE, TypeKind::Object, root_fields);
+
+        schema
+    }
+
+    pub fn get_type(&self, name: &str) -> Option<&TypeDef> {
+        self.types.get(name)
+    }
+
+    pub fn is_entity_type(&self, name: &str) -> bool {
+        self.entity_types.iter().any(|ty| ty == name)
+    }
+
+    fn add_type(&mut self, name: &str, kind: TypeKind, fields: Vec<FieldDef>) {
+        self.types.insert(name.to_owned(), TypeDef {
+            name: name.to_owned(),
+            kind,
+            description: None,
+            fields,
+            input_fields: vec![],
+            enum_values: vec![],
+        });
+    }
+
+    fn add_scalar(&mut self, name: &str, description: Option<&str>) {
+        self.add_type(name, TypeKind::Scalar, vec![]);
+
+        if let Some(ty) = self.types.get_mut(name) {
+            ty.description = description.map(str::to_owned);
+        }
+    }
+
+    fn add_enum(&mut self, name: &str, values: &[&str]) {
+        self.add_type(name, TypeKind::Enum, vec![]);
+
+        if let Some(ty) = self.types.get_mut(name) {
+            ty.enum_values = values.iter().map(|v| v.to_string()).collect();
+        }
+    }
+
+    fn add_builtins(&mut self) {
+        for scalar in &["ID", "String", "Int", "Float", "Boolean"] {
+            self.add_scalar(scalar, None);
+        }
+
+        self.add_scalar("Date", Some("ISO-8601 date, e.g. \"2020-05-27\"."));
+        self.add_scalar("DateTime", Some("RFC 3339 date and time, e.g. \"2020-05-27T12:00:00+00:00\"."));
+        self.add_scalar("JSON", Some("Any JSON value, such as the filters of /query."));
+
+        self.add_enum("SortOrder", &["asc", "desc"]);
+        self.add_enum("MissingPlacement", &["first", "last"]);
+
+        self.add_type("SortKey", TypeKind::InputObject, vec![]);
+
+        if let Some(ty) = self.types.get_mut("SortKey") {
+            ty.input_fields = vec![
+                input("by", TypeRef::named("String").non_null(), Some("Field to sort by, e.g. \"seo.title\".")),
+                input("order", TypeRef::named("SortOrder"), None),
+                input("missing", TypeRef::named("MissingPlacement"), Some("Where entities without the field are placed.")),
+            ];
+        }
+
+        for name in &["skip", "include"] {
+            self.directives.push(DirectiveDef {
+                name: name.to_string(),
+                description: None,
+                locations: vec!["FIELD", "FRAGMENT_SPREAD", "INLINE_FRAGMENT"],
+                args: vec![input("if", TypeRef::named("Boolean").non_null(), None)],
+            });
+        }
+    }
+
+    /// Field definitions of declared fields, adding the types of their objects.
+    fn field_defs(
+        &mut self,
+        parent: &str,
+        decls: &HashMap<String, FieldDeclaration>
+    ) -> Vec<FieldDef> {
+        let mut names: Vec<&String> = decls.keys().filter(|name| is_name(name)).collect();
+        names.sort();
+
+        names.into_iter()
+            .filter_map(|name| {
+                let decl = &decls[name];
+
+                let ty = match &decl.backlink {
+                    Some(backlink) => self.ref_type(&backlink.ty).non_null().list().non_null(),
+                    None => self.field_type(&format!("{parent}_{name}"), &decl.ty, decl)?
+                };
+
+                let ty = match ty {
+                    TypeRef::NonNull(_) => ty,
+                    _ if decl.required => ty.non_null(),
+                    _ => ty
+                };
+
+                Some(FieldDef {
+                    name: name.to_owned(),
+                    description: None,
+                    args: vec![],
+                    ty,
+                })
+            })
+            .collect()
+    }
+
+    /// Type of a declared field, or `None` if the field is an object whose
+    /// type name is taken, which leaves the field out.
+    fn field_type(&mut self, object_name: &str, ty: &FieldType, decl: &FieldDeclaration) -> Option<TypeRef> {
+        let ty = match ty {
+            FieldType::Str | FieldType::Enum => TypeRef::named("String"),
+            FieldType::Bin => TypeRef::named("String"),
+            FieldType::Num => TypeRef::named("Float"),
+            FieldType::Bool => TypeRef::named("Boolean"),
+            FieldType::Date => TypeRef::named("Date"),
+            FieldType::DateTime => TypeRef::named("DateTime"),
+            FieldType::Ref(ref_ty) => self.ref_type(ref_ty),
+            FieldType::List(item_ty) => self.field_type(object_name, item_ty, decl)?.list(),
+
+            FieldType::Object => {
+                // e.g. the "seo" object of "Post" and an entity type "Post_seo"
+                let taken = self.types.contains_key(object_name) ||
+                    self.entity_types.iter().any(|ty| *ty == object_name || page_type(ty) == object_name);
+
+                if taken {
+                    self.warnings.push(format!(
+                        r#"object type "{object_name}" was left out of the GraphQL schema, as its name is taken"#
+                    ));
+                    return None;
+                }
+
+                let fields = self.field_defs(object_name, &decl.fields);
+                self.add_type(object_name, TypeKind::Object, fields);
+
+                TypeRef::named(object_name)
+            },
+
+            FieldType::Backlink => TypeRef::named("ID").list()
+        };
+
+        Some(ty)
+    }
+
+    /// References to types left out of the schema are given as IDs.
+    fn ref_type(&self, ty: &str) -> TypeRef {
+        match self.is_entity_type(ty) {
+            true => TypeRef::named(ty),
+            false => TypeRef::named("ID")
+        }
+    }
+}
+
+fn field(name: &str, ty: TypeRef, description: &str) -> FieldDef {
+    FieldDef {
+        name: name.to_owned(),
+        description: Some(description.to_owned()),
+        args: vec![],
+        ty,
+    }
+}
+
+fn input(name: &str, ty: TypeRef, description: Option<&str>) -> InputValueDef {
+    InputValueDef {
+        name: name.to_owned(),
+        description: description.map(str::to_owned),
+        ty,
+    }
+}
+
+fn list_args() -> Vec<InputValueDef> {
+    vec![
+        input("filter", TypeRef::named("JSON"), Some("Filter in the format of /query, e.g. { rating: { gte: 3 } }.")),
+        input("search", TypeRef::named("String"), Some("Full-text search the entities must match.")),
+        input("sort", TypeRef::named("SortKey").non_null().list(), None),
+        input("limit", TypeRef::named("Int"), None),
+        input("offset", TypeRef::named("Int"), None),
+        input("cursor", TypeRef::named("String"), Some("Cursor from the next or prev of a previous page.")),
+    ]
+}
+
+/// Name of the type listing a page of entities of `ty`.
+pub fn page_type(ty: &str) -> String {
+    format!("{ty}Page")
+}
+
+/// Name of the root field getting one entity of `ty`, e.g. `post` for `Post`.
+pub fn entity_field(ty: &str) -> String {
+    let mut chars = ty.chars();
+
+    match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => String::new()
+    }
+}
+
+/// Name of the root field listing entities of `ty`, e.g. `allPost` for `Post`.
+pub fn list_field(ty: &str) -> String {
+    format!("all{ty}")
+}
+
+/// Whether `name` is a valid GraphQL name.
+pub fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    match chars.next() {
+        Some(first) if first == '_' || first.is_ascii_alphabetic() =>
+            chars.all(|c| c == '_' || c.is_ascii_alphanumeric()) && !name.starts_with("__"),
+
+        _ => false
+    }
+}
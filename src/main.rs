@@ -5,14 +5,21 @@
     proc_macro_hygiene,
 )]
 
+mod aggregate;
+mod asset;
 mod cache;
 mod cli;
+mod conditional;
 mod entity;
 mod error;
+mod filter;
+mod graphql;
+mod mime;
 mod parse;
 mod providers;
 mod server;
 mod schema;
+mod search;
 mod query;
 
 use crate::cli::CliArgs;
@@ -27,9 +34,16 @@ fn main() {
         port: args.port,
     });
 
-    let provider = FsProvider::new(FsProviderConfig {
+    let provider = match FsProvider::new(FsProviderConfig {
         root: args.content_path,
-    });
+        stream_threshold: args.stream_threshold,
+    }) {
+        Ok(provider) => provider,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    };
 
     server.listen(provider);
 }
@@ -0,0 +1,110 @@
+use crate::entity::FieldData;
+
+const OCTET_STREAM: &str = "application/octet-stream";
+
+/// Media types of well-known file extensions.
+const EXTENSIONS: &[(&str, &str)] = &[
+    ("md", "text/markdown"),
+    ("markdown", "text/markdown"),
+    ("txt", "text/plain"),
+    ("html", "text/html"),
+    ("htm", "text/html"),
+    ("css", "text/css"),
+    ("csv", "text/csv"),
+    ("js", "application/javascript"),
+    ("json", "application/json"),
+    ("toml", "application/toml"),
+    ("xml", "application/xml"),
+    ("pdf", "application/pdf"),
+    ("zip", "application/zip"),
+    ("gz", "application/gzip"),
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("avif", "image/avif"),
+    ("heic", "image/heic"),
+    ("svg", "image/svg+xml"),
+    ("ico", "image/x-icon"),
+    ("bmp", "image/bmp"),
+    ("mp3", "audio/mpeg"),
+    ("ogg", "audio/ogg"),
+    ("wav", "audio/wav"),
+    ("m4a", "audio/mp4"),
+    ("mp4", "video/mp4"),
+    ("mov", "video/quicktime"),
+    ("webm", "video/webm"),
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+];
+
+/// Media type of a file extension, ignoring case.
+pub fn from_extension(extension: &str) -> Option<&'static str> {
+    let extension = extension.to_ascii_lowercase();
+
+    EXTENSIONS.iter()
+        .find(|(ext, _)| *ext == extension)
+        .map(|(_, mime)| *mime)
+}
+
+/// Media type of binary data, recognized by the magic bytes it starts with.
+pub fn sniff(data: &[u8]) -> Option<&'static str> {
+    let riff = |format: &[u8]| data.starts_with(b"RIFF") && data.get(8..12) == Some(format);
+    let ftyp = |brands: &[&[u8]]| data.get(4..8) == Some(b"ftyp") &&
+        brands.iter().any(|brand| data.get(8..12) == Some(brand));
+
+    // Bitmaps are told apart from text starting with "BM" by their reserved
+    // bytes and the size of their info header
+    let bmp = data.starts_with(b"BM") && data.get(6..10) == Some(&[0; 4]) &&
+        matches!(data.get(14..18), Some([12, 0, 0, 0]) | Some([40, 0, 0, 0]) | Some([52, 0, 0, 0]) |
+            Some([56, 0, 0, 0]) | Some([64, 0, 0, 0]) | Some([108, 0, 0, 0]) | Some([124, 0, 0, 0]));
+
+    let mime = match data {
+        [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n', ..] => "image/png",
+        [0xff, 0xd8, 0xff, ..] => "image/jpeg",
+        [b'G', b'I', b'F', b'8', b'7', b'a', ..] | [b'G', b'I', b'F', b'8', b'9', b'a', ..] => "image/gif",
+        _ if riff(b"WEBP") => "image/webp",
+        _ if ftyp(&[b"avif", b"avis"]) => "image/avif",
+        _ if ftyp(&[b"heic", b"heix", b"mif1"]) => "image/heic",
+        [0x00, 0x00, 0x01, 0x00, ..] => "image/x-icon",
+        _ if bmp => "image/bmp",
+        [b'%', b'P', b'D', b'F', b'-', ..] => "application/pdf",
+        [b'P', b'K', 0x03, 0x04, ..] => "application/zip",
+        [0x1f, 0x8b, ..] => "application/gzip",
+        [b'I', b'D', b'3', ..] | [0xff, 0xfb, ..] => "audio/mpeg",
+        [b'O', b'g', b'g', b'S', ..] => "audio/ogg",
+        _ if riff(b"WAVE") => "audio/wav",
+        [0x1a, 0x45, 0xdf, 0xa3, ..] => "video/webm",
+        _ if ftyp(&[b"M4A "]) => "audio/mp4",
+        _ if ftyp(&[b"qt  "]) => "video/quicktime",
+        _ if ftyp(&[b"isom", b"iso2", b"mp41", b"mp42", b"avc1", b"M4V "]) => "video/mp4",
+        [b'w', b'O', b'F', b'F', ..] => "font/woff",
+        [b'w', b'O', b'F', b'2', ..] => "font/woff2",
+
+        _ => return None
+    };
+
+    Some(mime)
+}
+
+/// Content type to serve a field with, given the extension of the file it was
+/// read from, if any. Binary data is recognized by its content first, since
+/// extensions can be wrong; text is always UTF-8.
+pub fn content_type(data: &FieldData, extension: Option<&str>) -> String {
+    let from_extension = || extension.and_then(from_extension);
+
+    match data {
+        FieldData::Bin(bytes) => sniff(bytes)
+            .or_else(from_extension)
+            .unwrap_or(OCTET_STREAM)
+            .to_owned(),
+
+        FieldData::File(file) => file.mime
+            .or_else(from_extension)
+            .unwrap_or(OCTET_STREAM)
+            .to_owned(),
+
+        _ => format!("{}; charset=utf-8", from_extension().unwrap_or("text/plain"))
+    }
+}
@@ -15,7 +15,7 @@ pub trait FromKeyAndVal {
 
 struct KeyValOrStruct<'a, T>(&'a str, PhantomData<T>);
 
-impl<'de, T> de::DeserializeSeed<'de> for KeyValOrStruct<'de, T>
+impl<'de, 'a, T> de::DeserializeSeed<'de> for KeyValOrStruct<'a, T>
 where
     T: Deserialize<'de> + FromKeyAndVal
 {
@@ -41,8 +41,7 @@ where
             where
                 E: de::Error
             {
-                // TODO: Fix this unwrap
-                Ok(T::from_key_and_val(self.0, val).unwrap())
+                T::from_key_and_val(self.0, val).map_err(de::Error::custom)
             }
 
             fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
@@ -87,10 +86,9 @@ where
         {
             let mut out = HashMap::new();
 
-            while let Ok(Some(key)) = map.next_key::<&str>() {
-                if let Ok(val) = map.next_value_seed(KeyValOrStruct(key, PhantomData)) {
-                    out.insert(key.to_owned(), val);
-                }
+            while let Some(key) = map.next_key::<String>()? {
+                let val = map.next_value_seed(KeyValOrStruct(&key, PhantomData))?;
+                out.insert(key, val);
             }
 
             Ok(out)
@@ -99,3 +97,42 @@ where
 
     deser.deserialize_any(KeyValVisitor { marker: PhantomData })
 }
+
+/// Deserializes either a single value or a list of values into a list.
+pub fn one_or_many<'de, T, D>(deser: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    struct OneOrManyVisitor<T> {
+        marker: PhantomData<T>
+    }
+
+    impl<'de, T> de::Visitor<'de> for OneOrManyVisitor<T>
+    where
+        T: Deserialize<'de>
+    {
+        type Value = Vec<T>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            write!(formatter, "map or list of maps")
+        }
+
+        fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+        where
+            A: de::MapAccess<'de>
+        {
+            let val = Deserialize::deserialize(de::value::MapAccessDeserializer::new(map))?;
+            Ok(vec![val])
+        }
+
+        fn visit_seq<A>(self, seq: A) -> Result<Self::Value, A::Error>
+        where
+            A: de::SeqAccess<'de>
+        {
+            Deserialize::deserialize(de::value::SeqAccessDeserializer::new(seq))
+        }
+    }
+
+    deser.deserialize_any(OneOrManyVisitor { marker: PhantomData })
+}
@@ -7,22 +7,27 @@ use std::{
     },
     error::Error,
     fs::File,
+    io::{self, Read},
     path::Path,
     str::FromStr,
-    time::Duration,
+    time::{Duration, SystemTime, UNIX_EPOCH},
     thread,
     panic,
+    fmt,
 };
 
 use notify::{Watcher, RecursiveMode, watcher};
 use walkdir::{WalkDir, DirEntry};
 
 use crate::{
-    entity::{Entity, FieldData},
+    entity::{BinFile, Entity, FieldData},
     cache::Cache,
     schema::EntityDeclaration,
-    providers::Provider,
-    error::StringError,
+    providers::{Provider, ReloadFailure, ReloadStatus},
+    error::{StringError, ValidationReport},
+    asset,
+    conditional::{content_hash, ContentHasher},
+    mime,
 };
 
 pub struct RestartThread {
@@ -33,7 +38,11 @@ impl RestartThread {
     pub fn new<F: (Fn()) + Send + panic::UnwindSafe + Clone + 'static>(func: F) -> RestartThread {
         let join_handle = thread::spawn(move || {
             loop {
-                panic::catch_unwind(func.clone()).unwrap();
+                if panic::catch_unwind(func.clone()).is_err() {
+                    // Back off so a persistent failure doesn't spin
+                    eprintln!("Restarting thread after panic");
+                    thread::sleep(Duration::from_secs(1));
+                }
             }
         });
 
@@ -50,22 +59,33 @@ impl RestartThread {
 #[derive(Clone)]
 pub struct FsProviderConfig {
     pub root: String,
+
+    /// Size in bytes above which binary files are streamed from disk when
+    /// requested instead of being held in memory.
+    pub stream_threshold: u64,
 }
 
 pub struct FsProvider {
     cache: Arc<RwLock<Cache>>,
+    status: Arc<RwLock<ReloadStatus>>,
     restart_thread: RestartThread
 }
 
-fn decl_found(path: &Path, cache: &mut Cache) {
+fn load_error<E: fmt::Display>(path: &Path) -> impl FnOnce(E) -> Box<dyn Error> + '_ {
+    move |err| Box::new(StringError::new(&format!("{}: {err}", path.display())))
+}
+
+fn decl_found(path: &Path, cache: &mut Cache, stream_threshold: u64) -> Result<(), Box<dyn Error>> {
     let decl_name = path
         .file_name().unwrap()
         .to_str().unwrap();
 
     // Found a declaration, deserialize and send to cache
     let schema_path = path.join("schema");
-    let contents = std::fs::read_to_string(schema_path).unwrap();
-    let decl = EntityDeclaration::from_str(&contents).unwrap();
+    let contents = std::fs::read_to_string(&schema_path).map_err(load_error(&schema_path))?;
+    let mut decl = EntityDeclaration::from_str(&contents).map_err(load_error(&schema_path))?;
+    decl.source = schema_path;
+    decl.hash = content_hash(contents.as_bytes());
 
     cache.add_type(decl_name, decl);
 
@@ -79,8 +99,9 @@ fn decl_found(path: &Path, cache: &mut Cache) {
         let path = ent_entry.path();
 
         // Found an entity, deserialize and send to cache
-        let contents = std::fs::read_to_string(path).unwrap();
-        let ent = Entity::from_str(&contents).unwrap();
+        let contents = std::fs::read_to_string(path).map_err(load_error(path))?;
+        let mut ent = Entity::from_str(&contents).map_err(load_error(path))?;
+        ent.source = path.to_owned();
 
         let ent_name = ent_entry.path()
             .file_stem().unwrap()
@@ -102,8 +123,9 @@ fn decl_found(path: &Path, cache: &mut Cache) {
 
         // Found an entity, deserialize and send to cache
         let ent_path = path.join("ent");
-        let contents = std::fs::read_to_string(&ent_path).unwrap();
-        let mut ent = Entity::from_str(&contents).unwrap();
+        let contents = std::fs::read_to_string(&ent_path).map_err(load_error(&ent_path))?;
+        let mut ent = Entity::from_str(&contents).map_err(load_error(&ent_path))?;
+        ent.source = ent_path;
 
         // Search for any other fields
         for field_entry in WalkDir::new(path)
@@ -116,21 +138,40 @@ fn decl_found(path: &Path, cache: &mut Cache) {
                 .file_stem().unwrap()
                 .to_str().unwrap();
 
-            match std::fs::read_to_string(field_entry.path()) {
-                Ok(contents) => {
-                    ent.fields.insert(field_name.to_owned(), FieldData::Str(contents));
-                },
+            if let Some(extension) = file_extension(field_entry.path()) {
+                ent.extensions.insert(field_name.to_owned(), extension);
+            }
+
+            ent.fields.insert(field_name.to_owned(), read_field_file(field_entry.path(), stream_threshold)?);
+        }
 
-                _ => {
-                    use std::io::Read;
+        // Folders of files are list fields, e.g. a gallery of images
+        for list_entry in WalkDir::new(path)
+            .min_depth(1).max_depth(1).into_iter()
+            .filter_entry(|e| !is_hidden(e) && e.file_type().is_dir())
+            .flatten()
+        {
+            let field_name = list_entry.path()
+                .file_name().unwrap()
+                .to_str().unwrap();
 
-                    let mut file = File::open(field_entry.path()).unwrap();
-                    let mut data = Vec::new();
-                    file.read_to_end(&mut data).unwrap();
-                    ent.fields.insert(field_name.to_owned(), FieldData::Bin(data));
+            let mut items = Vec::new();
+
+            for item_entry in WalkDir::new(list_entry.path())
+                .min_depth(1).max_depth(1)
+                .sort_by(|a, b| a.file_name().cmp(b.file_name()))
+                .into_iter()
+                .filter_entry(|e| !is_hidden(e) && e.file_type().is_file())
+                .flatten()
+            {
+                if let Some(extension) = file_extension(item_entry.path()) {
+                    ent.extensions.insert(format!("{field_name}/{}", items.len()), extension);
                 }
+
+                items.push(read_field_file(item_entry.path(), stream_threshold)?);
             }
 
+            ent.fields.insert(field_name.to_owned(), FieldData::List(items));
         }
 
         // Add the entity
@@ -140,9 +181,100 @@ fn decl_found(path: &Path, cache: &mut Cache) {
 
         cache.add_entity(decl_name, ent_name, ent);
     }
+
+    Ok(())
+}
+
+fn file_extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_owned)
 }
 
-fn create_cache(base_path: &Path) -> Cache {
+/// Reads a file as a string field, or as a binary field if it isn't UTF-8.
+/// Binary files larger than `stream_threshold` bytes are left on disk.
+fn read_field_file(path: &Path, stream_threshold: u64) -> Result<FieldData, Box<dyn Error>> {
+    let size = std::fs::metadata(path).map_err(load_error(path))?.len();
+
+    if size > stream_threshold {
+        if let Some(file) = scan_bin_file(path).map_err(load_error(path))? {
+            return Ok(FieldData::File(file));
+        }
+    }
+
+    match std::fs::read_to_string(path) {
+        Ok(contents) => Ok(FieldData::Str(contents)),
+
+        _ => {
+            let mut data = Vec::new();
+            File::open(path)
+                .and_then(|mut file| file.read_to_end(&mut data))
+                .map_err(load_error(path))?;
+
+            Ok(FieldData::Bin(data))
+        }
+    }
+}
+
+/// Reads through a file in chunks to describe it without holding onto its
+/// content, or returns `None` if it's UTF-8 text, which is always read whole.
+fn scan_bin_file(path: &Path) -> io::Result<Option<BinFile>> {
+    let mut file = File::open(path)?;
+    let modified = file.metadata()?.modified().ok();
+    let mut chunk = vec![0; 64 * 1024];
+
+    let mut size = 0;
+    let mut hasher = ContentHasher::default();
+    let mut mime = None;
+    let mut dimensions = None;
+
+    // Bytes not yet known to be valid UTF-8, e.g. a character split across
+    // two chunks, or `None` once the file is known not to be text
+    let mut unchecked = Some(Vec::new());
+
+    loop {
+        let len = file.read(&mut chunk)?;
+
+        if len == 0 {
+            break;
+        }
+
+        let bytes = &chunk[..len];
+
+        if size == 0 {
+            mime = mime::sniff(bytes);
+            dimensions = asset::dimensions(bytes);
+        }
+
+        size += len as u64;
+        hasher.update(bytes);
+
+        if let Some(pending) = &mut unchecked {
+            pending.extend_from_slice(bytes);
+
+            match std::str::from_utf8(pending) {
+                Ok(_) => pending.clear(),
+                Err(e) if e.error_len().is_none() => { pending.drain(..e.valid_up_to()); },
+                Err(_) => unchecked = None
+            }
+        }
+    }
+
+    if unchecked.map_or(false, |pending| pending.is_empty()) {
+        return Ok(None);
+    }
+
+    Ok(Some(BinFile {
+        path: path.to_owned(),
+        size,
+        hash: hasher.finish(),
+        mime,
+        dimensions,
+        modified,
+    }))
+}
+
+fn create_cache(base_path: &Path, stream_threshold: u64) -> Result<Cache, Box<dyn Error>> {
     // Create a new cache
     let mut cache = Cache::new();
 
@@ -153,31 +285,85 @@ fn create_cache(base_path: &Path) -> Cache {
         .flatten()
         .filter(is_decl_folder)
     {
-       decl_found(folder_entry.path(), &mut cache);
+       decl_found(folder_entry.path(), &mut cache, stream_threshold)?;
     }
 
-    cache
+    cache.index_backlinks();
+    cache.index_search();
+    cache.index_hashes();
+    cache.generate_graphql_schema();
+
+    Ok(cache)
+}
+
+/// Loads and validates a fresh cache from the filesystem.
+fn load_cache(base_path: &Path, stream_threshold: u64) -> Result<Cache, Box<dyn Error>> {
+    Ok(create_cache(base_path, stream_threshold)?.validated()?)
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
 }
 
 impl FsProvider {
-    pub fn new(config: FsProviderConfig) -> FsProvider {
+    pub fn new(config: FsProviderConfig) -> Result<FsProvider, Box<dyn Error>> {
         // Convert the relative path in config to an absolute path
-        let base_path = Path::new(&config.root).canonicalize().unwrap();
+        let root = Path::new(&config.root);
+        let base_path = root.canonicalize().map_err(load_error(root))?;
+        let stream_threshold = config.stream_threshold;
 
         // Create an initial cache
         let cache_lock = {
-            let cache = create_cache(&base_path).validated();
+            let cache = load_cache(&base_path, stream_threshold)?;
 
             Arc::new(RwLock::new(cache))
         };
 
-        let update_cache = {
+        let status_lock = Arc::new(RwLock::new(ReloadStatus {
+            generation: 1,
+            last_success: Some(unix_time()),
+            last_failure: None,
+            warnings: cache_lock.read().unwrap().graphql_schema().warnings.clone(),
+        }));
+
+        // Only swap the new cache in if it loaded and validated, otherwise
+        // keep serving the previous one and record why the reload failed
+        let reload_cache = {
             let cache_lock = cache_lock.clone();
+            let status_lock = status_lock.clone();
+
+            move |base_path: &Path| {
+                let result = load_cache(base_path, stream_threshold);
+                let mut status = status_lock.write().unwrap();
+
+                match result {
+                    Ok(new_cache) => {
+                        status.warnings = new_cache.graphql_schema().warnings.clone();
+
+                        *cache_lock.write().unwrap() = new_cache;
+
+                        status.generation += 1;
 
-            move |new_cache: Cache| {
-                let mut guard = cache_lock.write().unwrap();
+                        status.last_success = Some(unix_time());
+                        status.last_failure = None;
+                    },
 
-                *guard = new_cache;
+                    Err(err) => {
+                        eprintln!("Failed to reload cache, keeping previous content:\n{err}");
+
+                        let errors = match err.downcast_ref::<ValidationReport>() {
+                            Some(report) => report.errors.iter().map(|e| e.to_string()).collect(),
+                            None => vec![err.to_string()]
+                        };
+
+                        status.last_failure = Some(ReloadFailure {
+                            time: unix_time(),
+                            errors,
+                        });
+                    }
+                }
             }
         };
 
@@ -197,18 +383,19 @@ impl FsProvider {
                     match event {
                         Write(_) | Create(_) |
                         Remove(_) | Rename(_, _) |
-                        Rescan => update_cache(create_cache(&base_path).validated()),
-                        
+                        Rescan => reload_cache(&base_path),
+
                         _ => {}
                     };
                 }
             })
         };
 
-        FsProvider {
+        Ok(FsProvider {
             cache: cache_lock.clone(),
+            status: status_lock.clone(),
             restart_thread,
-        }
+        })
     }
 }
 
@@ -219,6 +406,14 @@ impl Provider for FsProvider {
         )
     }
 
+    fn status(&self) -> Result<ReloadStatus, Box<dyn Error>> {
+        self.status.read()
+            .map(|status| status.clone())
+            .map_err(
+                |_| Box::new(StringError::new("Failed to acquire read-lock on status")) as Box<dyn Error>
+            )
+    }
+
     fn join(self) {
         self.restart_thread.join()
     }
@@ -1,10 +1,34 @@
 use std::sync::RwLockReadGuard;
 use std::error::Error;
 
+use serde::Serialize;
+
 use crate::cache::Cache;
 
+/// Outcome of the most recent cache (re)loads of a [Provider].
+#[derive(Clone, Debug, Serialize)]
+pub struct ReloadStatus {
+    /// Number of caches successfully loaded so far.
+    pub generation: u64,
+    /// Unix timestamp of the last successful load.
+    pub last_success: Option<u64>,
+    /// Set if the most recent reload failed and stale content is being served.
+    pub last_failure: Option<ReloadFailure>,
+    /// Problems with the content being served which didn't stop it loading,
+    /// such as types left out of the GraphQL schema.
+    pub warnings: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ReloadFailure {
+    /// Unix timestamp of the failed reload.
+    pub time: u64,
+    pub errors: Vec<String>,
+}
+
 pub trait Provider {
     fn read_cache(&self) -> Result<RwLockReadGuard<Cache>, Box<dyn Error>>;
+    fn status(&self) -> Result<ReloadStatus, Box<dyn Error>>;
     fn join(self);
 }
 
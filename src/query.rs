@@ -1,20 +1,115 @@
+use std::cmp::Ordering;
 use std::collections::HashMap;
 
+use chrono::{DateTime, FixedOffset, NaiveDate};
 use serde::{Deserialize, Serialize};
 
-use crate::entity::{Entity, FieldData};
+use crate::aggregate::{Aggregate, AggregateResult};
+use crate::asset::{Asset, FieldRoute};
+use crate::entity::{Entity, FieldData, FieldType};
 use crate::cache::Cache;
+use crate::error::QueryError;
+use crate::filter::Filter;
+use crate::parse::one_or_many;
+use crate::schema::{EntityDeclaration, FieldDeclaration};
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+impl Default for SortOrder {
+    fn default() -> Self {
+        SortOrder::Asc
+    }
+}
+
+/// Where entities without the sorted field are placed, regardless of the order.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MissingPlacement {
+    First,
+    Last,
+}
+
+impl Default for MissingPlacement {
+    fn default() -> Self {
+        MissingPlacement::Last
+    }
+}
 
 #[derive(Default, Debug, Deserialize)]
 pub struct QuerySortOptions {
-    by: String
+    /// Field to sort by, which may be a path into objects or `id`.
+    by: String,
+
+    #[serde(default)]
+    order: SortOrder,
+
+    #[serde(default)]
+    missing: MissingPlacement,
+}
+
+impl QuerySortOptions {
+    fn compare(&self, path: &[&str], (lhs_id, lhs): (&str, &Entity), (rhs_id, rhs): (&str, &Entity)) -> Ordering {
+        let ordering = match path {
+            ["id"] => lhs_id.cmp(rhs_id),
+
+            _ => match (lhs.get_path(path), rhs.get_path(path)) {
+                (Some(lhs), Some(rhs)) => lhs.compare(rhs).unwrap_or(Ordering::Equal),
+                (None, None) => Ordering::Equal,
+
+                // Missing values aren't affected by the order
+                (lhs, _) => return match (lhs.is_none(), self.missing) {
+                    (true, MissingPlacement::First) | (false, MissingPlacement::Last) => Ordering::Less,
+                    _ => Ordering::Greater
+                }
+            }
+        };
+
+        match self.order {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse()
+        }
+    }
 }
 
 #[derive(Default, Debug, Deserialize)]
 pub struct QueryEntity {
-    pub sort: Option<QuerySortOptions>,
-    pub filter: Option<HashMap<String, String>>,
-    pub fields: Vec<String>
+    /// Keys to sort by, in order of priority. Ties are broken by ID.
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub sort: Vec<QuerySortOptions>,
+
+    pub filter: Option<Filter>,
+
+    /// Full-text search the entities must match. Results are ordered by
+    /// relevance unless `sort` is given.
+    pub search: Option<String>,
+
+    pub fields: Vec<String>,
+
+    /// Levels of references to expand into the referenced entities.
+    #[serde(default)]
+    pub depth: usize,
+
+    /// Maximum number of entities to return, or all of them if `None`.
+    pub limit: Option<usize>,
+
+    /// Number of entities to skip before the first one returned.
+    #[serde(default)]
+    pub offset: usize,
+
+    /// Cursor from the `next` or `prev` of a previous result, used instead of `offset`.
+    pub cursor: Option<String>,
+
+    pub aggregate: Option<Aggregate>,
+
+    /// Whether binary fields include their content, base64-encoded, alongside
+    /// the URL serving it. Streamed files are only served by their URL.
+    #[serde(default)]
+    pub base64: bool,
 }
 
 #[derive(Default, Debug, Deserialize)]
@@ -26,7 +121,58 @@ pub struct Query {
 #[derive(Default, Debug, Serialize)]
 pub struct QueryResult<'a> {
     #[serde(flatten)]
-    pub groups: HashMap<&'a str, Vec<QueryResultEntity<'a>>>
+    pub groups: HashMap<&'a str, QueryResultGroup<'a>>
+}
+
+#[derive(Default, Debug, Serialize)]
+pub struct QueryResultGroup<'a> {
+    /// Number of entities matching the filter across all pages.
+    pub total: usize,
+
+    /// Cursors of the following and preceding pages, if there are any.
+    pub next: Option<String>,
+    pub prev: Option<String>,
+
+    pub entities: Vec<QueryResultEntity<'a>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aggregations: Option<AggregateResult<'a>>
+}
+
+/// Position in the sorted entities of a query, relative to an entity so pages
+/// stay in place when entities are added before them.
+#[derive(Debug)]
+enum Cursor {
+    After(String),
+    Before(String),
+}
+
+impl Cursor {
+    /// Encodes the cursor as an opaque, URL-safe token.
+    fn encode(&self) -> String {
+        let raw = match self {
+            Cursor::After(id) => format!("after:{id}"),
+            Cursor::Before(id) => format!("before:{id}")
+        };
+
+        raw.bytes().map(|b| format!("{b:02x}")).collect()
+    }
+
+    fn decode(token: &str) -> Option<Cursor> {
+        let bytes = (0..token.len()).step_by(2)
+            .map(|i| token.get(i..i + 2).and_then(|b| u8::from_str_radix(b, 16).ok()))
+            .collect::<Option<Vec<u8>>>()?;
+
+        let raw = String::from_utf8(bytes).ok()?;
+        let mut parts = raw.splitn(2, ':');
+
+        match (parts.next(), parts.next()) {
+            (Some("after"), Some(id)) => Some(Cursor::After(id.to_owned())),
+            (Some("before"), Some(id)) => Some(Cursor::Before(id.to_owned())),
+
+            _ => None
+        }
+    }
 }
 
 #[derive(Default, Debug, Serialize)]
@@ -38,18 +184,193 @@ pub struct QueryResultEntity<'a> {
 }
 
 impl<'a> QueryResultEntity<'a> {
-    pub fn filter_fields(&mut self, fields: &Vec<&str>) {
-        self.fields.retain(|name, _| fields.contains(name));
+    /// Builds the result for an entity, expanding references into the
+    /// referenced entities `depth` levels deep, or wherever the selection names
+    /// fields of the referenced entity.
+    pub fn resolve(
+        cache: &'a Cache,
+        ty: &'a str,
+        id: &'a str,
+        entity: &'a Entity,
+        selection: &Selection,
+        depth: usize,
+        base64: bool
+    ) -> Self {
+        let mut resolver = Resolver { cache, base64, path: vec![] };
+
+        resolver.resolve_entity(ty, id, entity, selection, depth.min(MAX_DEPTH))
     }
 }
 
-impl<'a> From<(&'a str, &'a Entity)> for QueryResultEntity<'a> {
-    fn from(data: (&'a str, &'a Entity)) -> Self {
-        QueryResultEntity {
-            id: data.0,
-            fields: data.1.fields.iter()
-                .map(|(name, data)| (name.as_str(), data.into()))
-                .collect()
+/// Maximum number of levels of references expanded in a result.
+const MAX_DEPTH: usize = 8;
+
+static ALL_FIELDS: Selection<'static> = Selection { fields: None };
+
+/// Fields to return for an entity, built from paths such as `author.full_name`
+/// which select fields of referenced entities and objects.
+#[derive(Debug, Default)]
+pub struct Selection<'q> {
+    /// Selected fields and their own selections, or `None` if all fields are selected.
+    fields: Option<HashMap<&'q str, Selection<'q>>>,
+}
+
+impl<'q> Selection<'q> {
+    pub fn all() -> Self {
+        Selection { fields: None }
+    }
+
+    pub fn from_paths<I: IntoIterator<Item = &'q str>>(paths: I) -> Self {
+        let mut selection = Selection { fields: Some(HashMap::new()) };
+
+        for path in paths {
+            selection.add(path);
+        }
+
+        selection
+    }
+
+    fn add(&mut self, path: &'q str) {
+        let mut parts = path.splitn(2, '.');
+        let field = parts.next().unwrap_or_default();
+
+        let child = self.fields.get_or_insert_with(HashMap::new)
+            .entry(field)
+            .or_default();
+
+        if let Some(rest) = parts.next() {
+            child.add(rest);
+        }
+    }
+
+    fn get(&self, field: &str) -> Option<&Selection<'q>> {
+        match &self.fields {
+            Some(fields) => fields.get(field),
+            None => Some(&ALL_FIELDS)
+        }
+    }
+
+    /// Whether specific fields were selected, which always expands references.
+    fn is_explicit(&self) -> bool {
+        self.fields.is_some()
+    }
+}
+
+/// State shared while resolving the entities of a result.
+struct Resolver<'a> {
+    cache: &'a Cache,
+    base64: bool,
+
+    /// Entities currently being expanded, so reference cycles are returned as
+    /// IDs instead of being expanded again.
+    path: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> Resolver<'a> {
+    fn resolve_entity(
+        &mut self,
+        ty: &'a str,
+        id: &'a str,
+        entity: &'a Entity,
+        selection: &Selection,
+        depth: usize
+    ) -> QueryResultEntity<'a> {
+        let cache = self.cache;
+        let decls = cache.get_group(ty).map(|group| &group.declaration.fields);
+        let mut fields = HashMap::new();
+
+        self.path.push((ty, id));
+
+        for (name, decl) in decls.into_iter().flatten() {
+            let child = match selection.get(name) {
+                Some(child) => child,
+                None => continue
+            };
+
+            let route = FieldRoute { ty, id, entity, field: name, index: None };
+
+            let value = match (&decl.backlink, entity.fields.get(name)) {
+                (Some(backlink), _) => QueryResultFieldData::List(
+                    cache.get_backlinks(backlink, id).iter()
+                        .map(|ref_id| self.resolve_ref(&backlink.ty, ref_id, child, depth))
+                        .collect()
+                ),
+
+                (None, Some(data)) => self.resolve_value(decl, &decl.ty, data, child, depth, Some(route)),
+                (None, None) => continue
+            };
+
+            fields.insert(name.as_str(), value);
+        }
+
+        self.path.pop();
+
+        QueryResultEntity { id, fields }
+    }
+
+    /// `route` is where the value is served on its own, if it can be.
+    fn resolve_value(
+        &mut self,
+        decl: &'a FieldDeclaration,
+        ty: &'a FieldType,
+        data: &'a FieldData,
+        selection: &Selection,
+        depth: usize,
+        route: Option<FieldRoute<'a>>
+    ) -> QueryResultFieldData<'a> {
+        match (ty, data) {
+            (FieldType::Ref(ref_ty), FieldData::Str(ref_id)) =>
+                self.resolve_ref(ref_ty, ref_id, selection, depth),
+
+            // Lists of references expand each item as a single reference
+            (FieldType::List(item_ty), FieldData::List(items)) => QueryResultFieldData::List(
+                items.iter()
+                    .enumerate()
+                    .map(|(i, item)| {
+                        // Only items of top-level lists are served individually
+                        let route = match **item_ty {
+                            FieldType::List(_) => None,
+                            _ => route.map(|route| route.item(i))
+                        };
+
+                        self.resolve_value(decl, item_ty, item, selection, depth, route)
+                    })
+                    .collect()
+            ),
+
+            (FieldType::Object, FieldData::Object(fields)) => QueryResultFieldData::Object(
+                fields.iter()
+                    .filter_map(|(name, data)| {
+                        let child = selection.get(name)?;
+                        let field = decl.fields.get(name)?;
+
+                        Some((name.as_str(), self.resolve_value(field, &field.ty, data, child, depth, None)))
+                    })
+                    .collect()
+            ),
+
+            (_, FieldData::Bin(_)) | (_, FieldData::File(_)) =>
+                QueryResultFieldData::Asset(Asset { data, route, base64: self.base64 }),
+
+            _ => data.into()
+        }
+    }
+
+    fn resolve_ref(
+        &mut self,
+        ty: &'a str,
+        id: &'a str,
+        selection: &Selection,
+        depth: usize
+    ) -> QueryResultFieldData<'a> {
+        let expand = (depth > 0 || selection.is_explicit()) && !self.path.contains(&(ty, id));
+
+        match self.cache.get_group(ty).and_then(|group| group.entities.get_key_value(id)) {
+            Some((id, entity)) if expand => QueryResultFieldData::Entity(Box::new(
+                self.resolve_entity(ty, id, entity, selection, depth.saturating_sub(1))
+            )),
+
+            _ => QueryResultFieldData::Str(id)
         }
     }
 }
@@ -57,59 +378,412 @@ impl<'a> From<(&'a str, &'a Entity)> for QueryResultEntity<'a> {
 #[derive(Debug, Serialize)]
 #[serde(untagged)]
 pub enum QueryResultFieldData<'a> {
-    Str(&'a String),
-    Bin(&'a Vec<u8>),
-    Num(&'a f64)
+    Str(&'a str),
+    Asset(Asset<'a>),
+    Num(&'a f64),
+    Bool(&'a bool),
+    Date(&'a NaiveDate),
+    DateTime(&'a DateTime<FixedOffset>),
+    List(Vec<QueryResultFieldData<'a>>),
+    Object(HashMap<&'a str, QueryResultFieldData<'a>>),
+    Entity(Box<QueryResultEntity<'a>>)
 }
 
 impl<'a> From<&'a FieldData> for QueryResultFieldData<'a> {
     fn from(field_data: &'a FieldData) -> QueryResultFieldData<'a> {
         match field_data {
             FieldData::Str(ref d) => QueryResultFieldData::Str(d),
-            FieldData::Bin(ref d) => QueryResultFieldData::Bin(d),
-            FieldData::Num(ref d) => QueryResultFieldData::Num(d)
+            FieldData::Bin(_) | FieldData::File(_) =>
+                QueryResultFieldData::Asset(Asset { data: field_data, route: None, base64: false }),
+            FieldData::Num(ref d) => QueryResultFieldData::Num(d),
+            FieldData::Bool(ref d) => QueryResultFieldData::Bool(d),
+            FieldData::Date(ref d) => QueryResultFieldData::Date(d),
+            FieldData::DateTime(ref d) => QueryResultFieldData::DateTime(d),
+            FieldData::List(ref d) => QueryResultFieldData::List(d.iter().map(|d| d.into()).collect()),
+            FieldData::Object(ref d) => QueryResultFieldData::Object(d.iter()
+                .map(|(name, d)| (name.as_str(), d.into()))
+                .collect())
         }
     }
 }
 
+/// Range of the sorted entities on the requested page.
+fn page_range(entities: &[(&String, &Entity)], query_ent: &QueryEntity) -> Result<(usize, usize), QueryError> {
+    let limit = query_ent.limit.unwrap_or(entities.len());
+    let position = |id: &str| entities.iter().position(|(other, _)| *other == id);
+
+    let cursor = match &query_ent.cursor {
+        Some(_) if query_ent.offset > 0 =>
+            return Err(QueryError::InvalidPage("cursor and offset can't be combined".to_owned())),
+
+        Some(token) => Some(Cursor::decode(token)
+            .ok_or_else(|| QueryError::InvalidPage(format!(r#"invalid cursor "{token}""#)))?),
+
+        None => None
+    };
+
+    // Cursors refer to entities which may have been removed since
+    let missing = |id: &str| QueryError::InvalidPage(format!(r#"cursor entity "{id}" no longer matches"#));
+
+    let (start, end) = match cursor {
+        Some(Cursor::After(id)) => {
+            let start = position(&id).ok_or_else(|| missing(&id))? + 1;
+            (start, start.saturating_add(limit))
+        },
+
+        Some(Cursor::Before(id)) => {
+            let end = position(&id).ok_or_else(|| missing(&id))?;
+            (end.saturating_sub(limit), end)
+        },
+
+        None => (query_ent.offset, query_ent.offset.saturating_add(limit))
+    };
+
+    let len = entities.len();
+    Ok((start.min(len), end.min(len)))
+}
+
+/// Sorts entities by each of the sort keys in turn, then by ID so the order
+/// is the same on every query.
+fn sort_entities(
+    entities: &mut Vec<(&String, &Entity)>,
+    sort: &[QuerySortOptions],
+    decl: &EntityDeclaration
+) -> Result<(), QueryError> {
+    let mut keys = vec![];
+
+    for options in sort {
+        let path: Vec<&str> = options.by.split('.').collect();
+
+        if path != ["id"] {
+            let field = decl.get_field(&path)
+                .ok_or_else(|| QueryError::UnknownField(options.by.clone()))?;
+
+            match field.ty {
+                FieldType::Str | FieldType::Num | FieldType::Bool | FieldType::Date |
+                FieldType::DateTime | FieldType::Enum | FieldType::Ref(_) => (),
+
+                _ => return Err(QueryError::InvalidSort(
+                    format!(r#"can't sort by "{}" of type "{}""#, options.by, field.ty)
+                ))
+            }
+        }
+
+        keys.push((path, options));
+    }
+
+    entities.sort_by(|(lhs_id, lhs), (rhs_id, rhs)| keys.iter()
+        .map(|(path, options)| options.compare(path, (lhs_id, lhs), (rhs_id, rhs)))
+        .find(|ordering| *ordering != Ordering::Equal)
+        .unwrap_or_else(|| lhs_id.cmp(rhs_id)));
+
+    Ok(())
+}
+
+/// A page of the entities matching a [QueryEntity].
+pub struct Page<'a> {
+    /// Number of matching entities across all pages.
+    pub total: usize,
+
+    pub next: Option<String>,
+    pub prev: Option<String>,
+
+    pub entities: Vec<(&'a String, &'a Entity)>
+}
+
+impl QueryEntity {
+    /// Entities of type `ty` matching the filter and search, in sorted order.
+    pub fn select<'a>(&self, cache: &'a Cache, ty: &str) -> Result<Vec<(&'a String, &'a Entity)>, QueryError> {
+        let group = cache.get_group(ty)
+            .ok_or_else(|| QueryError::UnknownType(ty.to_owned()))?;
+
+        let condition = match &self.filter {
+            Some(filter) => Some(filter.compile(group)?),
+            None => None
+        };
+
+        // Resolve equality on the ID or a unique field through the index
+        // instead of scanning every entity
+        let indexed = condition.as_ref().and_then(|c| c.equalities().into_iter()
+            .find_map(|(field, value)| match (field, value) {
+                ("id", FieldData::Str(id)) => Some(group.entities.get_key_value(id)),
+                _ => group.get_unique_data(field, value)
+            }));
+
+        let mut candidates: Vec<(&String, &Entity)> = match indexed {
+            Some(found) => found.into_iter().collect(),
+            None => group.entities.iter().collect()
+        };
+
+        candidates.retain(|(id, entity)| condition.as_ref()
+            .map_or(true, |c| c.matches(id, entity)));
+
+        match &self.search {
+            Some(search) => {
+                let scores: HashMap<&str, f64> = cache.search(search, Some(ty)).into_iter()
+                    .map(|hit| (hit.id, hit.score))
+                    .collect();
+
+                candidates.retain(|(id, _)| scores.contains_key(id.as_str()));
+
+                if self.sort.is_empty() {
+                    candidates.sort_by(|(lhs, _), (rhs, _)| scores[rhs.as_str()]
+                        .partial_cmp(&scores[lhs.as_str()])
+                        .unwrap_or(Ordering::Equal)
+                        .then_with(|| lhs.cmp(rhs)));
+                } else {
+                    sort_entities(&mut candidates, &self.sort, &group.declaration)?;
+                }
+            },
+
+            None => sort_entities(&mut candidates, &self.sort, &group.declaration)?
+        }
+
+        Ok(candidates)
+    }
+
+    /// The requested page of entities returned by [QueryEntity::select].
+    pub fn page<'a>(&self, entities: &[(&'a String, &'a Entity)]) -> Result<Page<'a>, QueryError> {
+        let (start, end) = page_range(entities, self)?;
+        let page = &entities[start..end];
+
+        let next = match page.last() {
+            Some((id, _)) if end < entities.len() => Some(Cursor::After(id.to_string()).encode()),
+            _ => None
+        };
+
+        let prev = match page.first() {
+            Some((id, _)) if start > 0 => Some(Cursor::Before(id.to_string()).encode()),
+            _ => None
+        };
+
+        Ok(Page {
+            total: entities.len(),
+            next,
+            prev,
+            entities: page.to_vec()
+        })
+    }
+}
+
 impl Query {
-    pub fn evaluate<'a>(&'a self, cache: &'a Cache) -> QueryResult<'a> {
+    pub fn evaluate<'a>(&'a self, cache: &'a Cache) -> Result<QueryResult<'a>, QueryError> {
         let mut result = QueryResult::default();
 
         for (ty, query_ent) in &self.entities {
-            let group = cache.get_group(ty);
-            let mut group_result = vec!();
-
-            for (id, entity) in group.entities.iter()
-                .filter(|(id, _)| query_ent.filter.as_ref()
-                        .map_or_else(
-                            || true,
-                            |f| f.get("id").map_or(false, |ref s| s == id))
-                        )
-            {
-                let mut query_result_entity: QueryResultEntity = (id.as_str(), entity).into();
-                query_result_entity.filter_fields(&query_ent.fields.iter()
-                    .map(|s| s.as_ref())
-                    .collect());
-
-                group_result.push(query_result_entity);
-            }
+            let group = cache.get_group(ty)
+                .ok_or_else(|| QueryError::UnknownType(ty.to_owned()))?;
+            let selection = Selection::from_paths(query_ent.fields.iter().map(|s| s.as_str()));
 
-            // Sort
-            if let Some(sort_options) = &query_ent.sort {
-                group_result.sort_unstable_by(|a: &QueryResultEntity, b: &QueryResultEntity| {
-                    let (lhs, rhs) = match (b.fields.get(sort_options.by.as_str()).unwrap(), a.fields.get(sort_options.by.as_str()).unwrap()) {
-                        (QueryResultFieldData::Str(lhs), QueryResultFieldData::Str(rhs)) => (lhs, rhs),
-                        _ => panic!("Can only sort string types")
-                    };
+            let matches = query_ent.select(cache, ty)?;
 
-                    rhs.cmp(lhs)
-                });
-            }
+            let aggregations = match &query_ent.aggregate {
+                Some(aggregate) => Some(aggregate.evaluate(&matches, &group.declaration)?),
+                None => None
+            };
 
-            result.groups.insert(&ty, group_result);
+            let page = query_ent.page(&matches)?;
+
+            let entities = page.entities.iter()
+                .map(|(id, entity)| QueryResultEntity::resolve(
+                    cache, ty, id, entity, &selection, query_ent.depth, query_ent.base64
+                ))
+                .collect();
+
+            result.groups.insert(&ty, QueryResultGroup {
+                total: page.total,
+                next: page.next,
+                prev: page.prev,
+                entities,
+                aggregations
+            });
         }
 
-        result
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    /// Entities with the given IDs and no fields, in that order.
+    fn entities(ids: &[&str]) -> Vec<(String, Entity)> {
+        ids.iter()
+            .map(|id| (id.to_string(), Entity::from_str("").unwrap()))
+            .collect()
+    }
+
+    fn refs(entities: &[(String, Entity)]) -> Vec<(&String, &Entity)> {
+        entities.iter().map(|(id, entity)| (id, entity)).collect()
+    }
+
+    /// IDs of the entities below, sorted by `sort` given as in a query.
+    fn sorted(sort: serde_json::Value) -> Result<Vec<String>, QueryError> {
+        let decl = EntityDeclaration::from_str(r#"
+            [fields]
+            title = "str"
+            rating = "num"
+            tags = "[str]"
+            seo = { type = "object", fields = { rank = "num" } }
+        "#).unwrap();
+
+        let owned: Vec<(String, Entity)> = [
+            ("a", r#"title = "b"
+                rating = 3
+                seo = { rank = 2 }"#),
+            ("b", r#"title = "a"
+                rating = 5"#),
+            ("c", r#"title = "c"
+                rating = 3
+                seo = { rank = 1 }"#),
+            ("d", r#"title = "a""#),
+        ].iter()
+            .map(|(id, ent)| (id.to_string(), Entity::from_str(ent).unwrap()))
+            .collect();
+
+        let query_ent: QueryEntity = serde_json::from_value(serde_json::json!({ "fields": [], "sort": sort })).unwrap();
+
+        let mut entities = refs(&owned);
+        sort_entities(&mut entities, &query_ent.sort, &decl)?;
+
+        Ok(entities.into_iter().map(|(id, _)| id.clone()).collect())
+    }
+
+    fn page_ids(query_ent: &QueryEntity, entities: &[(&String, &Entity)]) -> Vec<String> {
+        let page = query_ent.page(entities).unwrap();
+        page.entities.iter().map(|(id, _)| id.to_string()).collect()
+    }
+
+    #[test]
+    fn cursor_round_trip() {
+        let token = Cursor::After("a:b c".to_owned()).encode();
+        assert!(token.bytes().all(|b| b.is_ascii_hexdigit()));
+        assert!(matches!(Cursor::decode(&token), Some(Cursor::After(id)) if id == "a:b c"));
+
+        let token = Cursor::Before("x".to_owned()).encode();
+        assert!(matches!(Cursor::decode(&token), Some(Cursor::Before(id)) if id == "x"));
+    }
+
+    #[test]
+    fn cursor_rejected() {
+        let encode = |raw: &[u8]| raw.iter().map(|b| format!("{b:02x}")).collect::<String>();
+
+        assert!(Cursor::decode("zz").is_none());
+        assert!(Cursor::decode(&encode(b"after:a")[1..]).is_none());
+        assert!(Cursor::decode(&encode(b"around:a")).is_none());
+        assert!(Cursor::decode(&encode(b"after")).is_none());
+        assert!(Cursor::decode(&encode(b"after:\xff")).is_none());
+    }
+
+    #[test]
+    fn page_range_offsets() {
+        let owned = entities(&["a", "b", "c", "d", "e"]);
+        let entities = refs(&owned);
+
+        let range = |limit, offset| page_range(&entities, &QueryEntity { limit, offset, ..Default::default() });
+
+        assert_eq!(range(None, 0).unwrap(), (0, 5));
+        assert_eq!(range(Some(2), 0).unwrap(), (0, 2));
+        assert_eq!(range(Some(2), 4).unwrap(), (4, 5));
+        assert_eq!(range(Some(2), 10).unwrap(), (5, 5));
+        assert_eq!(range(Some(0), 1).unwrap(), (1, 1));
+        assert_eq!(range(Some(usize::MAX), 1).unwrap(), (1, 5));
+    }
+
+    #[test]
+    fn page_range_cursors() {
+        let owned = entities(&["a", "b", "c", "d", "e"]);
+        let entities = refs(&owned);
+
+        let range = |cursor: Cursor, limit| page_range(&entities, &QueryEntity {
+            cursor: Some(cursor.encode()),
+            limit,
+            ..Default::default()
+        });
+
+        assert_eq!(range(Cursor::After("b".to_owned()), Some(2)).unwrap(), (2, 4));
+        assert_eq!(range(Cursor::After("e".to_owned()), Some(2)).unwrap(), (5, 5));
+        assert_eq!(range(Cursor::After("a".to_owned()), None).unwrap(), (1, 5));
+        assert_eq!(range(Cursor::Before("d".to_owned()), Some(2)).unwrap(), (1, 3));
+        assert_eq!(range(Cursor::Before("b".to_owned()), Some(2)).unwrap(), (0, 1));
+
+        assert!(matches!(range(Cursor::After("z".to_owned()), Some(2)), Err(QueryError::InvalidPage(_))));
+    }
+
+    #[test]
+    fn page_range_invalid() {
+        let owned = entities(&["a", "b"]);
+        let entities = refs(&owned);
+
+        let combined = QueryEntity {
+            cursor: Some(Cursor::After("a".to_owned()).encode()),
+            offset: 1,
+            ..Default::default()
+        };
+
+        let malformed = QueryEntity { cursor: Some("not a cursor".to_owned()), ..Default::default() };
+
+        assert!(matches!(page_range(&entities, &combined), Err(QueryError::InvalidPage(_))));
+        assert!(matches!(page_range(&entities, &malformed), Err(QueryError::InvalidPage(_))));
+    }
+
+    #[test]
+    fn page_cursors() {
+        let owned = entities(&["a", "b", "c", "d", "e"]);
+        let entities = refs(&owned);
+
+        // Walk forwards through every page, then back from the last one
+        let first = QueryEntity { limit: Some(2), ..Default::default() };
+        let page = first.page(&entities).unwrap();
+        assert_eq!(page_ids(&first, &entities), ["a", "b"]);
+        assert_eq!(page.total, 5);
+        assert!(page.prev.is_none());
+
+        let second = QueryEntity { limit: Some(2), cursor: page.next, ..Default::default() };
+        let page = second.page(&entities).unwrap();
+        assert_eq!(page_ids(&second, &entities), ["c", "d"]);
+
+        let last = QueryEntity { limit: Some(2), cursor: page.next, ..Default::default() };
+        let page = last.page(&entities).unwrap();
+        assert_eq!(page_ids(&last, &entities), ["e"]);
+        assert!(page.next.is_none());
+
+        let back = QueryEntity { limit: Some(2), cursor: page.prev, ..Default::default() };
+        assert_eq!(page_ids(&back, &entities), ["c", "d"]);
+    }
+
+    #[test]
+    fn sort_keys() {
+        // Ties are broken by ID
+        assert_eq!(sorted(serde_json::json!([])).unwrap(), ["a", "b", "c", "d"]);
+        assert_eq!(sorted(serde_json::json!({ "by": "title" })).unwrap(), ["b", "d", "a", "c"]);
+        assert_eq!(sorted(serde_json::json!({ "by": "id", "order": "desc" })).unwrap(), ["d", "c", "b", "a"]);
+
+        // Later keys order entities the earlier ones tie
+        assert_eq!(
+            sorted(serde_json::json!([{ "by": "rating" }, { "by": "title", "order": "desc" }])).unwrap(),
+            ["c", "a", "b", "d"]
+        );
+    }
+
+    #[test]
+    fn sort_missing() {
+        // Missing values are placed regardless of the order
+        assert_eq!(sorted(serde_json::json!({ "by": "rating", "order": "desc" })).unwrap(), ["b", "a", "c", "d"]);
+        assert_eq!(
+            sorted(serde_json::json!({ "by": "rating", "order": "desc", "missing": "first" })).unwrap(),
+            ["d", "b", "a", "c"]
+        );
+        assert_eq!(sorted(serde_json::json!({ "by": "seo.rank" })).unwrap(), ["c", "a", "b", "d"]);
+    }
+
+    #[test]
+    fn sort_invalid() {
+        assert!(matches!(sorted(serde_json::json!({ "by": "author" })), Err(QueryError::UnknownField(_))));
+        assert!(matches!(sorted(serde_json::json!({ "by": "tags" })), Err(QueryError::InvalidSort(_))));
+        assert!(matches!(sorted(serde_json::json!({ "by": "seo" })), Err(QueryError::InvalidSort(_))));
     }
 }
@@ -1,14 +1,97 @@
 use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
 use std::str::FromStr;
 
 use std::error::Error;
 
-use serde::Deserialize;
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::entity::{FieldType, FieldData};
 use crate::parse::{keyval_map, FromKeyAndVal};
 
-#[derive(Deserialize)]
+/// A field of another entity type which refers to entities of this type,
+/// declared as `"Type.field"`.
+pub struct Backlink {
+    pub ty: String,
+    pub field: String,
+}
+
+impl fmt::Display for Backlink {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}", self.ty, self.field)
+    }
+}
+
+impl<'de> Deserialize<'de> for Backlink {
+    fn deserialize<D>(deser: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>
+    {
+        let s = String::deserialize(deser)?;
+
+        let mut parts = s.splitn(2, '.');
+
+        match (parts.next(), parts.next()) {
+            (Some(ty), Some(field)) => Ok(Backlink {
+                ty: ty.to_owned(),
+                field: field.to_owned(),
+            }),
+
+            _ => Err(serde::de::Error::custom(
+                format!(r#"backlink "{s}" must be given as "Type.field""#)
+            ))
+        }
+    }
+}
+
+impl Serialize for Backlink {
+    fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer
+    {
+        ser.collect_str(self)
+    }
+}
+
+/// Fields declared without a type are backlinks.
+fn backlink_type() -> FieldType {
+    FieldType::Backlink
+}
+
+/// A regular expression string values must match, compiled when the schema is loaded.
+pub struct Pattern(pub Regex);
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Pattern {
+    fn deserialize<D>(deser: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>
+    {
+        let s = String::deserialize(deser)?;
+
+        Regex::new(&s)
+            .map(Pattern)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl Serialize for Pattern {
+    fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer
+    {
+        ser.serialize_str(self.0.as_str())
+    }
+}
+
+#[derive(Deserialize, Serialize)]
 pub struct FieldDeclaration {
     #[serde(default)]
     pub name: String,
@@ -17,8 +100,47 @@ pub struct FieldDeclaration {
     pub required: bool,
     #[serde(default)]
     pub mutable: bool,
-    #[serde(rename = "type")]
+    /// Whether no two entities may share a value, allowing lookups by the field.
+    #[serde(default)]
+    pub unique: bool,
+    /// Whether the field's text is indexed for full-text search.
+    #[serde(default)]
+    pub searchable: bool,
+    /// Reported when validated if a backlink is inferred without a `backlink`.
+    #[serde(rename = "type", default = "backlink_type")]
     pub ty: FieldType,
+
+    /// Field of another type whose referring entities this virtual field lists.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backlink: Option<Backlink>,
+
+    /// Value filled into entities which don't provide this field.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<FieldData>,
+
+    /// Allowed values of an enum field.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub values: Vec<String>,
+
+    /// Fields of an object field.
+    #[serde(default, deserialize_with = "keyval_map", skip_serializing_if = "HashMap::is_empty")]
+    pub fields: HashMap<String, FieldDeclaration>,
+
+    /// Bounds of a number, or of each number in a list.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+
+    /// Bounds of the number of characters in a string, or of items in a list.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_length: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_length: Option<usize>,
+
+    /// Pattern a string, or each string in a list, must match.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<Pattern>,
 }
 
 impl FromKeyAndVal for FieldDeclaration {
@@ -29,6 +151,18 @@ impl FromKeyAndVal for FieldDeclaration {
 
             required: false,
             mutable: false,
+            unique: false,
+            searchable: false,
+            backlink: None,
+            default: None,
+            values: Vec::new(),
+            fields: HashMap::new(),
+
+            min: None,
+            max: None,
+            min_length: None,
+            max_length: None,
+            pattern: None,
         })
     }
 
@@ -37,12 +171,39 @@ impl FromKeyAndVal for FieldDeclaration {
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 pub struct EntityDeclaration {
+    /// Path the schema was loaded from.
+    #[serde(skip)]
+    pub source: PathBuf,
+
+    /// Hash of the schema as written.
+    #[serde(skip)]
+    pub hash: String,
+
     #[serde(deserialize_with = "keyval_map")]
     pub fields: HashMap<String, FieldDeclaration>,
 }
 
+impl EntityDeclaration {
+    /// Gets the declaration of the field at `path`, descending into objects.
+    pub fn get_field(&self, path: &[&str]) -> Option<&FieldDeclaration> {
+        find_field(&self.fields, path)
+    }
+}
+
+fn find_field<'d>(fields: &'d HashMap<String, FieldDeclaration>, path: &[&str]) -> Option<&'d FieldDeclaration> {
+    let (first, rest) = path.split_first()?;
+    let field = fields.get(*first)?;
+
+    match (&field.ty, rest.is_empty()) {
+        (_, true) => Some(field),
+        (FieldType::Object, false) => find_field(&field.fields, rest),
+
+        _ => None
+    }
+}
+
 pub trait FromFieldData {
     fn from_field_data<'a>(ty: &'a FieldType, data: &'a FieldData) -> Option<&'a Self>;
 }
@@ -69,6 +230,17 @@ impl FromFieldData for f64 {
     }
 }
 
+impl FromFieldData for bool {
+    fn from_field_data<'a>(ty: &'a FieldType, data: &'a FieldData) -> Option<&'a Self> {
+        match (ty, data) {
+            (FieldType::Bool, FieldData::Bool(b)) =>
+                Some(&b),
+
+            _ => None
+        }
+    }
+}
+
 impl FromStr for EntityDeclaration {
     type Err = toml::de::Error;
 
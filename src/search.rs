@@ -0,0 +1,138 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use rust_stemmers::{Algorithm, Stemmer};
+use serde::Serialize;
+
+/// Words too common to be worth indexing.
+const STOP_WORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "by", "for", "from", "in", "is",
+    "it", "of", "on", "or", "that", "the", "this", "to", "was", "with",
+];
+
+// Ranking parameters of BM25
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+/// Splits text into lowercase, stemmed terms, skipping stop words and any
+/// markup between words.
+pub fn tokenize(stemmer: &Stemmer, text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .filter(|word| !STOP_WORDS.contains(&word.as_str()))
+        .map(|word| stemmer.stem(&word).into_owned())
+        .collect()
+}
+
+/// The searchable text of an entity.
+struct Document {
+    ty: String,
+    id: String,
+
+    /// Number of terms in the document.
+    len: usize,
+}
+
+/// Inverted index over the searchable fields of every entity.
+pub struct SearchIndex {
+    documents: Vec<Document>,
+
+    /// Documents containing each term, with the number of times it occurs.
+    postings: HashMap<String, Vec<(usize, usize)>>,
+
+    /// Stemmer of indexed text and queries, created once for the index.
+    stemmer: Stemmer,
+}
+
+impl Default for SearchIndex {
+    fn default() -> Self {
+        SearchIndex {
+            documents: Vec::new(),
+            postings: HashMap::new(),
+            stemmer: Stemmer::create(Algorithm::English),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchHit<'a> {
+    #[serde(rename = "type")]
+    pub ty: &'a str,
+    pub id: &'a str,
+    pub score: f64,
+}
+
+impl SearchIndex {
+    pub fn add_document<'t, I>(&mut self, ty: &str, id: &str, texts: I)
+    where
+        I: IntoIterator<Item = &'t str>
+    {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        let mut len = 0;
+
+        for term in texts.into_iter().flat_map(|text| tokenize(&self.stemmer, text)) {
+            *counts.entry(term).or_default() += 1;
+            len += 1;
+        }
+
+        if len == 0 {
+            return;
+        }
+
+        let doc = self.documents.len();
+
+        self.documents.push(Document {
+            ty: ty.to_owned(),
+            id: id.to_owned(),
+            len,
+        });
+
+        for (term, count) in counts {
+            self.postings.entry(term).or_default().push((doc, count));
+        }
+    }
+
+    /// Ranks the entities containing any term of `query` by relevance, most
+    /// relevant first. Only entities of type `ty` are returned if given.
+    pub fn search(&self, query: &str, ty: Option<&str>) -> Vec<SearchHit<'_>> {
+        let total = self.documents.len() as f64;
+        let avg_len = self.documents.iter().map(|d| d.len).sum::<usize>() as f64 / total.max(1.0);
+
+        let mut terms = tokenize(&self.stemmer, query);
+        terms.sort_unstable();
+        terms.dedup();
+
+        let mut scores: HashMap<usize, f64> = HashMap::new();
+
+        for term in terms {
+            let postings = match self.postings.get(&term) {
+                Some(postings) => postings,
+                None => continue
+            };
+
+            // Rarer terms weigh more
+            let found = postings.len() as f64;
+            let idf = ((total - found + 0.5) / (found + 0.5) + 1.0).ln();
+
+            for &(doc, count) in postings {
+                let tf = count as f64;
+                let norm = 1.0 - B + B * self.documents[doc].len as f64 / avg_len;
+
+                *scores.entry(doc).or_default() += idf * tf * (K1 + 1.0) / (tf + K1 * norm);
+            }
+        }
+
+        let mut hits: Vec<SearchHit> = scores.into_iter()
+            .map(|(doc, score)| (&self.documents[doc], score))
+            .filter(|(doc, _)| ty.map_or(true, |ty| doc.ty == ty))
+            .map(|(doc, score)| SearchHit { ty: &doc.ty, id: &doc.id, score })
+            .collect();
+
+        hits.sort_by(|lhs, rhs| rhs.score.partial_cmp(&lhs.score)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| (lhs.ty, lhs.id).cmp(&(rhs.ty, rhs.id))));
+
+        hits
+    }
+}
@@ -1,13 +1,33 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
 use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
 
-use rocket::http::Status;
+use rocket::http::{ContentType, Status};
+use rocket::request::{self, FromRequest};
+use rocket::response::{self, Content, Responder, Response};
+use serde::Serialize;
 
-use crate::entity::FieldData;
+use crate::cache::{Cache, TypeGroup};
+use crate::conditional::{self, ByteRange};
+use crate::entity::{BinFile, Entity, FieldData};
+use crate::error::ApiError;
+use crate::graphql;
+use crate::mime;
 use crate::providers::{Provider, FsProvider};
-use crate::query::{Query, QueryResultEntity};
+use crate::query::{Query, QueryResultEntity, Selection};
+use crate::schema::EntityDeclaration;
 
 const MAX_QUERY_LEN: u64 = 2048;
 
+// Introspection queries from GraphQL tools are much longer than most queries
+const MAX_GRAPHQL_LEN: u64 = 16384;
+
+// Content changes whenever the content folder does, so caches may store
+// responses but must revalidate them before each use
+const CACHE_CONTROL: &str = "public, no-cache";
+
 pub struct ServerConfig {
     pub bind_address: String,
     pub port: u16,
@@ -19,6 +39,315 @@ pub struct Server {
 
 type ProviderState<P> = Arc<RwLock<P>>;
 
+impl ApiError {
+    fn status(&self) -> Status {
+        match self {
+            ApiError::NotFound(_) => Status::NotFound,
+            ApiError::BadRequest(_) => Status::BadRequest,
+            ApiError::PayloadTooLarge(_) => Status::PayloadTooLarge,
+            ApiError::Internal => Status::InternalServerError
+        }
+    }
+}
+
+impl<'r> Responder<'r> for ApiError {
+    fn respond_to(self, _: &rocket::Request) -> response::Result<'r> {
+        let body = serde_json::json!({ "error": self.to_string() }).to_string();
+
+        Response::build()
+            .status(self.status())
+            .header(ContentType::JSON)
+            .sized_body(Cursor::new(body))
+            .ok()
+    }
+}
+
+/// Content of a response, either in memory or streamed from a file.
+enum Body {
+    Bytes(Vec<u8>),
+
+    /// A file opened when the request was handled, with its description.
+    File(BinFile, File),
+}
+
+trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+impl Body {
+    fn len(&self) -> u64 {
+        match self {
+            Body::Bytes(bytes) => bytes.len() as u64,
+            Body::File(bin_file, _) => bin_file.size
+        }
+    }
+
+    /// Reads the bytes from `start` up to but excluding `end`.
+    fn into_reader(self, start: u64, end: u64) -> io::Result<Box<dyn ReadSeek>> {
+        match self {
+            Body::Bytes(mut bytes) => {
+                bytes.truncate(end as usize);
+                bytes.drain(..start as usize);
+
+                Ok(Box::new(Cursor::new(bytes)))
+            },
+
+            Body::File(_, file) => Ok(Box::new(FileRange::new(file, start, end)?))
+        }
+    }
+}
+
+/// Part of a file, which reads and seeks as if it were the whole file so that
+/// its length can be found by seeking to the end.
+struct FileRange {
+    file: File,
+    start: u64,
+    end: u64,
+    pos: u64,
+}
+
+impl FileRange {
+    fn new(mut file: File, start: u64, end: u64) -> io::Result<FileRange> {
+        file.seek(SeekFrom::Start(start))?;
+
+        Ok(FileRange { file, start, end, pos: start })
+    }
+}
+
+impl Read for FileRange {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.end.saturating_sub(self.pos);
+        let len = remaining.min(buf.len() as u64) as usize;
+
+        let read = self.file.read(&mut buf[..len])?;
+        self.pos += read as u64;
+
+        Ok(read)
+    }
+}
+
+impl Seek for FileRange {
+    fn seek(&mut self, from: SeekFrom) -> io::Result<u64> {
+        let len = self.end - self.start;
+
+        let offset = match from {
+            SeekFrom::Start(offset) => offset as i64,
+            SeekFrom::End(delta) => len as i64 + delta,
+            SeekFrom::Current(delta) => (self.pos - self.start) as i64 + delta
+        };
+
+        if offset < 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "seek before start of range"));
+        }
+
+        self.pos = self.start + (offset as u64).min(len);
+        self.file.seek(SeekFrom::Start(self.pos))?;
+
+        Ok(self.pos - self.start)
+    }
+}
+
+/// Validators of the client's copy sent with a request, checked before the
+/// response is built.
+struct Conditions {
+    if_none_match: Option<String>,
+    if_modified_since: Option<String>,
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for Conditions {
+    type Error = ();
+
+    fn from_request(req: &'a rocket::Request<'r>) -> request::Outcome<Self, ()> {
+        let header = |name| req.headers().get_one(name).map(str::to_owned);
+
+        request::Outcome::Success(Conditions {
+            if_none_match: header("If-None-Match"),
+            if_modified_since: header("If-Modified-Since"),
+        })
+    }
+}
+
+/// Guard of lookups by unique fields, which forwards requests for the items of
+/// list fields of an entity with the ID "by" to the field item route instead.
+struct UniqueLookup;
+
+impl<'a, 'r> FromRequest<'a, 'r> for UniqueLookup {
+    type Error = ();
+
+    fn from_request(req: &'a rocket::Request<'r>) -> request::Outcome<Self, ()> {
+        let param = |n| req.get_param::<String>(n).and_then(Result::ok);
+
+        let (ty, field_name) = match (param(1), param(3)) {
+            (Some(ty), Some(field_name)) => (ty, field_name),
+            _ => return request::Outcome::Forward(())
+        };
+
+        let provider = match req.guard::<rocket::State<ProviderState<FsProvider>>>() {
+            request::Outcome::Success(provider) => provider,
+            _ => return request::Outcome::Forward(())
+        };
+
+        // Failures to read the cache are reported by the route itself
+        let shadows_item = match provider.read() {
+            Ok(provider) => provider.read_cache().ok().and_then(|cache| {
+                let group = cache.get_group(&ty)?;
+                let unique = group.declaration.fields.get(&field_name).map_or(false, |field| field.unique);
+
+                Some(group.get_entity("by").is_some() && !unique)
+            }),
+
+            Err(_) => None
+        };
+
+        match shadows_item {
+            Some(true) => request::Outcome::Forward(()),
+            _ => request::Outcome::Success(UniqueLookup)
+        }
+    }
+}
+
+/// A response sent with an `ETag` and `Last-Modified`, or `304 Not Modified`
+/// without content if the client's copy is still fresh. Responses accepting
+/// ranges are answered with `206 Partial Content` for `Range` requests.
+struct Cached {
+    etag: String,
+    last_modified: SystemTime,
+    accept_ranges: bool,
+
+    /// Type and content of the response, or `None` if it's not modified.
+    content: Option<(ContentType, Body)>,
+}
+
+impl Cached {
+    /// Builds the response with `content` unless the client's copy is fresh.
+    /// The entity tag combines the hashes of everything the response is built
+    /// from, which were computed when `cache` loaded.
+    fn new<F>(
+        conditions: &Conditions,
+        cache: &Cache,
+        hashes: &[&str],
+        accept_ranges: bool,
+        content: F
+    ) -> Result<Cached, ApiError>
+    where
+        F: FnOnce() -> Result<(ContentType, Body), ApiError>
+    {
+        let etag = format!(r#""{}""#, hashes.join("-"));
+        let last_modified = cache.loaded_at();
+
+        let fresh = conditional::is_fresh(
+            conditions.if_none_match.as_deref(),
+            conditions.if_modified_since.as_deref(),
+            &etag,
+            last_modified
+        );
+
+        let content = match fresh {
+            true => None,
+            false => Some(content()?)
+        };
+
+        Ok(Cached { etag, last_modified, accept_ranges, content })
+    }
+}
+
+impl<'r> Responder<'r> for Cached {
+    fn respond_to(self, req: &rocket::Request) -> response::Result<'r> {
+        let headers = req.headers();
+
+        let mut response = match self.content {
+            None => Response::build().status(Status::NotModified).finalize(),
+
+            Some((content_type, body)) => {
+                let len = body.len();
+
+                // Ranges of a different version than the client's partial copy
+                // can't be combined with it, so the whole content is sent instead
+                let range = match headers.get_one("Range") {
+                    Some(range) if self.accept_ranges &&
+                        conditional::if_range_matches(headers.get_one("If-Range"), &self.etag, self.last_modified) =>
+                        conditional::parse_range(range, len),
+
+                    _ => None
+                };
+
+                match range {
+                    Some(ByteRange::Partial(start, end)) => Response::build()
+                        .status(Status::PartialContent)
+                        .header(content_type)
+                        .raw_header("Content-Range", format!("bytes {start}-{}/{len}", end - 1))
+                        .sized_body(body.into_reader(start, end).map_err(|err| internal(err).status())?)
+                        .finalize(),
+
+                    Some(ByteRange::Unsatisfiable) => Response::build()
+                        .status(Status::RangeNotSatisfiable)
+                        .raw_header("Content-Range", format!("bytes */{len}"))
+                        .finalize(),
+
+                    None => Response::build()
+                        .header(content_type)
+                        .sized_body(body.into_reader(0, len).map_err(|err| internal(err).status())?)
+                        .finalize()
+                }
+            }
+        };
+
+        response.set_raw_header("ETag", self.etag);
+        response.set_raw_header("Last-Modified", conditional::http_date(self.last_modified));
+        response.set_raw_header("Cache-Control", CACHE_CONTROL);
+
+        if self.accept_ranges {
+            response.set_raw_header("Accept-Ranges", "bytes");
+        }
+
+        Ok(response)
+    }
+}
+
+/// Logs an internal fault, which is returned without details.
+fn internal<E: std::fmt::Display>(err: E) -> ApiError {
+    eprintln!("Internal error: {err}");
+
+    ApiError::Internal
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<Content<Vec<u8>>, ApiError> {
+    serde_json::to_vec(value)
+        .map(|json| Content(ContentType::JSON, json))
+        .map_err(internal)
+}
+
+fn json_content<T: Serialize>(value: &T) -> Result<(ContentType, Body), ApiError> {
+    to_json(value).map(|Content(content_type, json)| (content_type, Body::Bytes(json)))
+}
+
+fn find_group<'c>(cache: &'c Cache, ty: &str) -> Result<&'c TypeGroup, ApiError> {
+    cache.get_group(ty)
+        .ok_or_else(|| ApiError::NotFound(format!(r#"no such type "{ty}""#)))
+}
+
+fn find_entity<'c>(group: &'c TypeGroup, ty: &str, ent_id: &str) -> Result<&'c Entity, ApiError> {
+    group.get_entity(ent_id)
+        .ok_or_else(|| ApiError::NotFound(format!(r#"no such entity "{ent_id}" of type "{ty}""#)))
+}
+
+/// Reads a request body of up to `limit` bytes.
+fn read_body(data: rocket::Data, limit: u64) -> Result<String, ApiError> {
+    use std::io::Read;
+
+    // Read one byte past the limit to tell if the body was cut off
+    let mut bytes = vec![];
+    data.open().take(limit + 1).read_to_end(&mut bytes)
+        .map_err(|err| ApiError::BadRequest(format!("failed to read request body: {err}")))?;
+
+    if bytes.len() as u64 > limit {
+        return Err(ApiError::PayloadTooLarge(limit));
+    }
+
+    String::from_utf8(bytes)
+        .map_err(|_| ApiError::BadRequest("request body isn't valid UTF-8".to_owned()))
+}
+
 #[rocket::get("/")]
 fn get_index() -> String {
     let version = env!("CARGO_PKG_VERSION");
@@ -26,36 +355,169 @@ fn get_index() -> String {
     format!("micro-cms version {version}")
 }
 
-#[rocket::get("/ent/<ty>/<ent_id>?<fields>")]
+#[rocket::get("/status")]
+fn get_status(
+    provider: rocket::State<ProviderState<FsProvider>>
+) -> Result<Content<Vec<u8>>, ApiError> {
+    let provider = match provider.read() {
+        Ok(p) => p,
+        Err(err) => return Err(internal(err))
+    };
+
+    let status = match provider.status() {
+        Ok(s) => s,
+        Err(err) => return Err(internal(err))
+    };
+
+    to_json(&status)
+}
+
+#[rocket::get("/schema")]
+fn get_schema(
+    provider: rocket::State<ProviderState<FsProvider>>
+) -> Result<Content<Vec<u8>>, ApiError> {
+    let provider = match provider.read() {
+        Ok(p) => p,
+        Err(err) => return Err(internal(err))
+    };
+
+    let cache = match provider.read_cache() {
+        Ok(p) => p,
+        Err(err) => return Err(internal(err))
+    };
+
+    let schema: HashMap<&str, &EntityDeclaration> = cache.groups()
+        .map(|(name, group)| (name.as_str(), &group.declaration))
+        .collect();
+
+    to_json(&schema)
+}
+
+#[rocket::get("/search?<q>&<ty>&<limit>")]
+fn search(
+    q: String,
+    ty: Option<String>,
+    limit: Option<usize>,
+    provider: rocket::State<ProviderState<FsProvider>>
+) -> Result<Content<Vec<u8>>, ApiError> {
+    let provider = match provider.read() {
+        Ok(p) => p,
+        Err(err) => return Err(internal(err))
+    };
+
+    let cache = match provider.read_cache() {
+        Ok(p) => p,
+        Err(err) => return Err(internal(err))
+    };
+
+    let mut hits = cache.search(&q, ty.as_deref());
+
+    if let Some(limit) = limit {
+        hits.truncate(limit);
+    }
+
+    to_json(&hits)
+}
+
+#[rocket::get("/ent/<ty>/<ent_id>?<fields>&<depth>&<base64>")]
 fn get_entity(
     ty: String,
     ent_id: String,
     fields: Option<String>,
+    depth: Option<usize>,
+    base64: Option<bool>,
+    conditions: Conditions,
+    provider: rocket::State<ProviderState<FsProvider>>
+) -> Result<Cached, ApiError> {
+    let provider = match provider.read() {
+        Ok(p) => p,
+        Err(err) => return Err(internal(err))
+    };
+
+    let cache = match provider.read_cache() {
+        Ok(p) => p,
+        Err(err) => return Err(internal(err))
+    };
+
+    let group = find_group(&cache, &ty)?;
+    let ent = find_entity(group, &ty, &ent_id)?;
+
+    entity_response(&cache, &conditions, &ty, &ent_id, ent, EntityOptions { fields, depth, base64 })
+}
+
+#[rocket::get("/ent/<ty>/by/<field_name>/<value>?<fields>&<depth>&<base64>")]
+fn get_entity_by(
+    ty: String,
+    field_name: String,
+    value: String,
+    fields: Option<String>,
+    depth: Option<usize>,
+    base64: Option<bool>,
+    _lookup: UniqueLookup,
+    conditions: Conditions,
     provider: rocket::State<ProviderState<FsProvider>>
-) -> Result<Vec<u8>, Status> {
+) -> Result<Cached, ApiError> {
     let provider = match provider.read() {
         Ok(p) => p,
-        _ => return Err(Status::BadRequest)
+        Err(err) => return Err(internal(err))
     };
 
     let cache = match provider.read_cache() {
         Ok(p) => p,
-        _ => return Err(Status::BadRequest)
+        Err(err) => return Err(internal(err))
     };
 
-    let group = cache.get_group(&ty);
+    let group = find_group(&cache, &ty)?;
+
+    match group.get_unique(&field_name, &value) {
+        Some(Some((ent_id, ent))) =>
+            entity_response(&cache, &conditions, &ty, ent_id, ent, EntityOptions { fields, depth, base64 }),
 
-    let query_tup = (ent_id.as_str(), group.get_entity(&ent_id));
-    let mut response_ent: QueryResultEntity = query_tup.into();
+        Some(None) => Err(ApiError::NotFound(
+            format!(r#"no entity of type "{ty}" has "{field_name}" "{value}""#)
+        )),
 
-    // Filter the fields that we got back
-    if let Some(fields_str) = fields {
-        let fields: Vec<_> = fields_str.split(',').collect();
-        response_ent.filter_fields(&fields);
+        None if group.declaration.fields.contains_key(&field_name) => Err(ApiError::BadRequest(
+            format!(r#"field "{field_name}" isn't unique"#)
+        )),
+
+        None => Err(ApiError::NotFound(format!(r#"no such field "{field_name}""#)))
     }
+}
+
+/// Query parameters of the entity routes.
+#[derive(Debug)]
+struct EntityOptions {
+    fields: Option<String>,
+    depth: Option<usize>,
+    base64: Option<bool>,
+}
+
+fn entity_response(
+    cache: &Cache,
+    conditions: &Conditions,
+    ty: &str,
+    ent_id: &str,
+    ent: &Entity,
+    options: EntityOptions
+) -> Result<Cached, ApiError> {
+    // Expanded references and backlinks read other entities, so responses
+    // change with any content, as well as with the entity and options
+    let request_hash = conditional::content_hash(format!("{ty}/{ent_id}?{options:?}").as_bytes());
 
-    let response_str = serde_json::to_string(&response_ent).unwrap();
-    Ok(response_str.into())
+    Cached::new(conditions, cache, &[cache.hash(), &request_hash], false, || {
+        // Select the fields that we get back
+        let selection = match &options.fields {
+            Some(fields_str) => Selection::from_paths(fields_str.split(',')),
+            None => Selection::all()
+        };
+
+        let response_ent = QueryResultEntity::resolve(
+            cache, ty, ent_id, ent, &selection, options.depth.unwrap_or(0), options.base64.unwrap_or(false)
+        );
+
+        json_content(&response_ent)
+    })
 }
 
 #[rocket::get("/ent/<ty>/<ent_id>/<field_name>")]
@@ -63,62 +525,179 @@ fn get_field(
     ty: String,
     ent_id: String,
     field_name: String,
+    conditions: Conditions,
     provider: rocket::State<ProviderState<FsProvider>>
-) -> Result<Vec<u8>, Status> {
+) -> Result<Cached, ApiError> {
     let provider = match provider.read() {
         Ok(p) => p,
-        _ => return Err(Status::BadRequest)
+        Err(err) => return Err(internal(err))
     };
 
     let cache = match provider.read_cache() {
         Ok(p) => p,
-        _ => return Err(Status::BadRequest)
+        Err(err) => return Err(internal(err))
     };
 
     // Get everything before dot path seperator
     let tokens: Vec<&str> = field_name.split('.').collect();
     let field_name = tokens[0];
 
-    let group = cache.get_group(&ty);
-    let ent = group.get_entity(&ent_id);
-    let field_data = ent.fields.get(field_name);
+    let group = find_group(&cache, &ty)?;
+    let ent = find_entity(group, &ty, &ent_id)?;
+
+    field_response(&cache, &conditions, ent, field_name, field_name, find_field(ent, field_name)?)
+}
+
+#[rocket::get("/ent/<ty>/<ent_id>/<field_name>/<index>")]
+fn get_field_item(
+    ty: String,
+    ent_id: String,
+    field_name: String,
+    index: usize,
+    conditions: Conditions,
+    provider: rocket::State<ProviderState<FsProvider>>
+) -> Result<Cached, ApiError> {
+    let provider = match provider.read() {
+        Ok(p) => p,
+        Err(err) => return Err(internal(err))
+    };
 
-    match field_data {
-        Some(FieldData::Str(d)) => Ok(d.clone().into()),
-        Some(FieldData::Bin(d)) => Ok(d.clone()),
-        _ => Err(Status::BadRequest)
+    let cache = match provider.read_cache() {
+        Ok(p) => p,
+        Err(err) => return Err(internal(err))
+    };
+
+    // Get everything before dot path seperator
+    let tokens: Vec<&str> = field_name.split('.').collect();
+    let field_name = tokens[0];
+
+    let group = find_group(&cache, &ty)?;
+    let ent = find_entity(group, &ty, &ent_id)?;
+
+    match find_field(ent, field_name)? {
+        FieldData::List(items) => match items.get(index) {
+            Some(item) =>
+                field_response(&cache, &conditions, ent, &format!("{field_name}/{index}"), field_name, item),
+            None => Err(ApiError::NotFound(format!(r#"no item {index} in field "{field_name}""#)))
+        },
+
+        _ => Err(ApiError::BadRequest(format!(r#"field "{field_name}" isn't a list"#)))
     }
 }
 
+fn find_field<'e>(ent: &'e Entity, field_name: &str) -> Result<&'e FieldData, ApiError> {
+    ent.fields.get(field_name)
+        .ok_or_else(|| ApiError::NotFound(format!(r#"no such field "{field_name}""#)))
+}
+
+/// Serves a text or binary field with the content type of the file it was read
+/// from, which is recorded in the entity under `key`.
+fn field_response(
+    cache: &Cache,
+    conditions: &Conditions,
+    ent: &Entity,
+    key: &str,
+    field_name: &str,
+    field_data: &FieldData
+) -> Result<Cached, ApiError> {
+    if !matches!(field_data, FieldData::Str(_) | FieldData::Bin(_) | FieldData::File(_)) {
+        return Err(ApiError::BadRequest(
+            format!(r#"field "{field_name}" of type "{}" can't be served directly"#, field_data.type_name())
+        ));
+    }
+
+    let hash = ent.field_hashes.get(key).map_or("", String::as_str);
+
+    // Media players seek through binary fields such as audio and video
+    let accept_ranges = !matches!(field_data, FieldData::Str(_));
+
+    Cached::new(conditions, cache, &[hash], accept_ranges, || {
+        let content_type = mime::content_type(field_data, ent.extensions.get(key).map(|ext| ext.as_str()));
+        let content_type = ContentType::parse_flexible(&content_type).unwrap_or(ContentType::Binary);
+
+        // Large binary fields are streamed from disk rather than copied per request,
+        // unless the file changed since it was loaded, e.g. while a failed reload
+        // keeps the previous content being served
+        let body = match field_data {
+            FieldData::File(file) => Body::File(file.clone(), file.open().map_err(internal)?),
+            FieldData::Bin(d) => Body::Bytes(d.clone()),
+            FieldData::Str(d) => Body::Bytes(d.clone().into()),
+
+            _ => unreachable!()
+        };
+
+        Ok((content_type, body))
+    })
+}
+
 #[rocket::post("/query", data = "<input_data>")]
 fn query(
     input_data: rocket::Data,
+    conditions: Conditions,
     provider: rocket::State<ProviderState<FsProvider>>
-) -> Result<Vec<u8>, Status>  {
-    let input = {
-        use std::io::Read;
+) -> Result<Cached, ApiError>  {
+    let input = read_body(input_data, MAX_QUERY_LEN)?;
+
+    let provider = match provider.read() {
+        Ok(p) => p,
+        Err(err) => return Err(internal(err))
+    };
+
+    let cache = match provider.read_cache() {
+        Ok(p) => p,
+        Err(err) => return Err(internal(err))
+    };
 
-        let mut output = String::new();
-        input_data.open().take(MAX_QUERY_LEN).read_to_string(&mut output).expect("Failed to read data stream into string");
-        output
+    let query: Query = match serde_json::from_str(&input) {
+        Ok(q) => q,
+        Err(err) => return Err(ApiError::BadRequest(format!("malformed query: {err}")))
     };
 
+    let query_hash = conditional::content_hash(input.as_bytes());
+
+    Cached::new(&conditions, &cache, &[cache.hash(), &query_hash], false, || {
+        // Evaluate the query
+        let result = query.evaluate(&cache)?;
+
+        json_content(&result)
+    })
+}
+
+#[rocket::post("/graphql", data = "<input_data>")]
+fn graphql(
+    input_data: rocket::Data,
+    provider: rocket::State<ProviderState<FsProvider>>
+) -> Result<Content<Vec<u8>>, ApiError> {
+    let input = read_body(input_data, MAX_GRAPHQL_LEN)?;
+
     let provider = match provider.read() {
         Ok(p) => p,
-        _ => return Err(Status::BadRequest)
+        Err(err) => return Err(internal(err))
     };
 
     let cache = match provider.read_cache() {
         Ok(p) => p,
-        _ => return Err(Status::BadRequest)
+        Err(err) => return Err(internal(err))
+    };
+
+    let request: graphql::Request = match serde_json::from_str(&input) {
+        Ok(r) => r,
+        Err(err) => return Err(ApiError::BadRequest(format!("malformed request: {err}")))
     };
 
-    let query: Query = serde_json::from_str(&input).unwrap();
+    let response = graphql::execute(&cache, &request);
 
-    // Evaluate the query
-    let result = query.evaluate(&cache);
+    to_json(&response)
+}
+
+#[rocket::catch(404)]
+fn not_found(req: &rocket::Request) -> ApiError {
+    ApiError::NotFound(format!("no route for {}", req.uri()))
+}
 
-    Ok(serde_json::to_string(&result).unwrap().into())
+#[rocket::catch(500)]
+fn internal_error() -> ApiError {
+    ApiError::Internal
 }
 
 impl Server {
@@ -134,9 +713,16 @@ impl Server {
         rocket::ignite()
             .manage(Arc::clone(&provider_arc))
             .mount("/", rocket::routes![get_index])
+            .mount("/", rocket::routes![get_status])
+            .mount("/", rocket::routes![get_schema])
             .mount("/", rocket::routes![query])
+            .mount("/", rocket::routes![search])
+            .mount("/", rocket::routes![graphql])
             .mount("/", rocket::routes![get_field])
+            .mount("/", rocket::routes![get_field_item])
             .mount("/", rocket::routes![get_entity])
+            .mount("/", rocket::routes![get_entity_by])
+            .register(rocket::catchers![not_found, internal_error])
             .launch();
 
         // Join the provider before the server dies